@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_test::parse::parse_prog;
+
+// malformed input must come back as a parse error, never as a panic
+fuzz_target!(|data: &[u8]| {
+    if let Ok(src) = std::str::from_utf8(data) {
+        let _ = parse_prog(src);
+    }
+});
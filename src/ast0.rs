@@ -1,15 +1,22 @@
-#[derive(Debug)]
+use crate::error::Pos;
+
+#[derive(Debug, Clone)]
 pub enum Op1 {
     Neg,
     Not,
+    IntOfFloat,
+    FloatOfInt,
+    MkRef,
+    Deref,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Op2 {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
     Lte,
     Gte,
     Lt,
@@ -18,25 +25,39 @@ pub enum Op2 {
     Neq,
     And,
     Or,
+    Assign,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Term {
     Int(i32),
+    Float(f64),
     Bool(bool),
     Var(String),
-    Op1(Op1, Box<Term>),
-    Op2(Op2, Box<Term>, Box<Term>),
+    Op1(Op1, Box<Term>, Pos),
+    Op2(Op2, Box<Term>, Box<Term>, Pos),
     Fun(String, String, Box<Term>),
-    App(Box<Term>, Box<Term>),
+    App(Box<Term>, Box<Term>, Pos),
     LetIn(String, Box<Term>, Box<Term>),
     Ifte(Box<Term>, Box<Term>, Box<Term>),
+    Raise(Box<Term>, Pos),
+    Try(Box<Term>, String, Box<Term>),
+    Effect(String, Box<Term>),
+    Perform(String, Box<Term>, Pos),
+    Handle(Box<Term>, String, Box<Term>, Vec<Case>),
+    CallCC(Box<Term>, Pos),
 }
 
+pub type Case = (String, String, String, Box<Term>);
+
 pub fn int(i: i32) -> Box<Term> {
     Box::new(Term::Int(i))
 }
 
+pub fn float(f: f64) -> Box<Term> {
+    Box::new(Term::Float(f))
+}
+
 pub fn bool(b: bool) -> Box<Term> {
     Box::new(Term::Bool(b))
 }
@@ -45,20 +66,20 @@ pub fn var(s: String) -> Box<Term> {
     Box::new(Term::Var(s))
 }
 
-pub fn op1(op: Op1, m: Box<Term>) -> Box<Term> {
-    Box::new(Term::Op1(op, m))
+pub fn op1(op: Op1, m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op1(op, m, pos))
 }
 
-pub fn op2(op: Op2, m: Box<Term>, n: Box<Term>) -> Box<Term> {
-    Box::new(Term::Op2(op, m, n))
+pub fn op2(op: Op2, m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op2(op, m, n, pos))
 }
 
 pub fn fun(s1: String, s2: String, m: Box<Term>) -> Box<Term> {
     Box::new(Term::Fun(s1, s2, m))
 }
 
-pub fn app(m: Box<Term>, n: Box<Term>) -> Box<Term> {
-    Box::new(Term::App(m, n))
+pub fn app(m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::App(m, n, pos))
 }
 
 pub fn letin(s: String, m: Box<Term>, n: Box<Term>) -> Box<Term> {
@@ -68,3 +89,27 @@ pub fn letin(s: String, m: Box<Term>, n: Box<Term>) -> Box<Term> {
 pub fn ifte(m: Box<Term>, n1: Box<Term>, n2: Box<Term>) -> Box<Term> {
     Box::new(Term::Ifte(m, n1, n2))
 }
+
+pub fn raise(m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Raise(m, pos))
+}
+
+pub fn trywith(m: Box<Term>, s: String, n: Box<Term>) -> Box<Term> {
+    Box::new(Term::Try(m, s, n))
+}
+
+pub fn effect(s: String, m: Box<Term>) -> Box<Term> {
+    Box::new(Term::Effect(s, m))
+}
+
+pub fn perform(s: String, m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Perform(s, m, pos))
+}
+
+pub fn handle(m: Box<Term>, s: String, n: Box<Term>, cases: Vec<Case>) -> Box<Term> {
+    Box::new(Term::Handle(m, s, n, cases))
+}
+
+pub fn callcc(m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::CallCC(m, pos))
+}
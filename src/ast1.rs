@@ -1,17 +1,24 @@
-use crate::names::*;
+use crate::{error::Pos, names::*};
+use ahash::HashSet;
+use std::fmt::{self, Display};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Op1 {
     Neg,
     Not,
+    IntOfFloat,
+    FloatOfInt,
+    MkRef,
+    Deref,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Op2 {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
     Lte,
     Gte,
     Lt,
@@ -20,25 +27,228 @@ pub enum Op2 {
     Neq,
     And,
     Or,
+    Assign,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Term {
     Int(i32),
+    Float(f64),
     Bool(bool),
     Var(Name),
-    Op1(Op1, Box<Term>),
-    Op2(Op2, Box<Term>, Box<Term>),
+    Op1(Op1, Box<Term>, Pos),
+    Op2(Op2, Box<Term>, Box<Term>, Pos),
     Fun(Name, Name, Box<Term>),
-    App(Box<Term>, Box<Term>),
+    App(Box<Term>, Box<Term>, Pos),
     LetIn(Name, Box<Term>, Box<Term>),
     Ifte(Box<Term>, Box<Term>, Box<Term>),
+    Raise(Box<Term>, Pos),
+    Try(Box<Term>, Name, Box<Term>),
+    Effect(Name, Box<Term>),
+    Perform(Name, Box<Term>, Pos),
+    Handle(Box<Term>, Name, Box<Term>, Vec<Case>),
+    CallCC(Box<Term>, Pos),
+}
+
+pub type Case = (Name, Name, Name, Box<Term>);
+
+impl Display for Op1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Op1::*;
+        match self {
+            Neg => write!(f, "-"),
+            Not => write!(f, "!"),
+            IntOfFloat => write!(f, "int_of_float "),
+            FloatOfInt => write!(f, "float_of_int "),
+            MkRef => write!(f, "ref "),
+            Deref => write!(f, "!"),
+        }
+    }
+}
+
+impl Display for Op2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Op2::*;
+        match self {
+            Add => write!(f, "+"),
+            Sub => write!(f, "-"),
+            Mul => write!(f, "*"),
+            Div => write!(f, "/"),
+            Mod => write!(f, "%"),
+            Lte => write!(f, "<="),
+            Gte => write!(f, ">="),
+            Lt => write!(f, "<"),
+            Gt => write!(f, ">"),
+            Eq => write!(f, "=="),
+            Neq => write!(f, "!="),
+            And => write!(f, "&&"),
+            Or => write!(f, "||"),
+            Assign => write!(f, ":="),
+        }
+    }
+}
+
+// binding strength of the operators in parse.rs, from := (0) to application (7),
+// operands of prim ops, raise, perform and callcc are printed at 8
+fn prec(op: &Op2) -> u8 {
+    use Op2::*;
+    match op {
+        Assign => 0,
+        And | Or => 1,
+        Eq | Neq => 2,
+        Lte | Gte | Lt | Gt => 3,
+        Add | Sub => 4,
+        Mul | Div | Mod => 5,
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_term(self, 0, f)
+    }
+}
+
+fn fmt_term(m0: &Term, p: u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Term::*;
+    // let, fun, if, ... extend as far right as possible and prefix
+    // operators only parse at the start of a term
+    let open = matches!(
+        m0,
+        Op1(self::Op1::Neg | self::Op1::Not, ..)
+            | Fun(..)
+            | LetIn(..)
+            | Ifte(..)
+            | Try(..)
+            | Effect(..)
+            | Handle(..)
+    );
+    let atom = match m0 {
+        Int(i) => *i >= 0,
+        Float(x) => x.is_sign_positive() && !x.is_nan(),
+        _ => true,
+    };
+    let parens = (open && p > 0)
+        || !atom
+        || matches!(m0, Op2(op, ..) if prec(op) < p)
+        || matches!(m0, App(..) if p > 7);
+    if parens {
+        write!(f, "(")?;
+    }
+    match m0 {
+        // the grammar has no negative literals, these print as what parses
+        // back to the same value
+        Int(i32::MIN) => write!(f, "-{} - 1", i32::MAX)?,
+        Int(i) => write!(f, "{}", i)?,
+        Float(x) if x.is_nan() => write!(f, "0.0 / 0.0")?,
+        Float(x) if x.is_infinite() => write!(f, "{}1e999", if *x < 0.0 { "-" } else { "" })?,
+        Float(x) => write!(f, "{:?}", x)?,
+        Bool(b) => write!(f, "{}", b)?,
+        Var(x) => write!(f, "{}", x)?,
+        // `!` reads a reference only right before a variable or a
+        // parenthesis, and negates anything else
+        Op1(self::Op1::Deref, m, _) if matches!(**m, Var(_)) => write!(f, "!{}", m)?,
+        Op1(self::Op1::Deref, m, _) => write!(f, "!({})", m)?,
+        Op1(self::Op1::Not, m, _) => {
+            write!(f, "! ")?;
+            fmt_term(m, 8, f)?
+        }
+        Op1(op, m, _) => {
+            write!(f, "{}", op)?;
+            fmt_term(m, 8, f)?
+        }
+        Op2(self::Op2::Assign, m, n, _) => {
+            fmt_term(m, 1, f)?;
+            write!(f, " := ")?;
+            fmt_term(n, 0, f)?
+        }
+        Op2(op, m, n, _) => {
+            fmt_term(m, prec(op), f)?;
+            write!(f, " {} ", op)?;
+            fmt_term(n, prec(op) + 1, f)?
+        }
+        Fun(g, x, m) if g.to_string().is_empty() => {
+            write!(f, "fun {} => ", x)?;
+            fmt_term(m, 0, f)?
+        }
+        Fun(g, x, m) => {
+            write!(f, "let rec {} {} := ", g, x)?;
+            fmt_term(m, 0, f)?;
+            write!(f, " in {}", g)?
+        }
+        App(m, n, _) => {
+            fmt_term(m, 7, f)?;
+            write!(f, " ")?;
+            fmt_term(n, 8, f)?
+        }
+        LetIn(x, m, n) => {
+            match m.as_ref() {
+                Fun(g, y, m) if g == x => {
+                    write!(f, "let rec {} {} := ", g, y)?;
+                    fmt_term(m, 0, f)?
+                }
+                _ => {
+                    write!(f, "let {} := ", x)?;
+                    fmt_term(m, 0, f)?
+                }
+            }
+            write!(f, " in ")?;
+            fmt_term(n, 0, f)?
+        }
+        Ifte(m, n1, n2) => {
+            write!(f, "if ")?;
+            fmt_term(m, 0, f)?;
+            write!(f, " then ")?;
+            fmt_term(n1, 0, f)?;
+            write!(f, " else ")?;
+            fmt_term(n2, 0, f)?
+        }
+        Raise(m, _) => {
+            write!(f, "raise ")?;
+            fmt_term(m, 8, f)?
+        }
+        Try(m, x, n) => {
+            write!(f, "try ")?;
+            fmt_term(m, 0, f)?;
+            write!(f, " with {} => ", x)?;
+            fmt_term(n, 0, f)?
+        }
+        Effect(op, m) => {
+            write!(f, "effect {} in ", op)?;
+            fmt_term(m, 0, f)?
+        }
+        Perform(op, m, _) => {
+            write!(f, "perform {} ", op)?;
+            fmt_term(m, 8, f)?
+        }
+        Handle(m, x, n, cases) => {
+            write!(f, "handle ")?;
+            fmt_term(m, 0, f)?;
+            write!(f, " with | return {} => ", x)?;
+            fmt_term(n, 1, f)?;
+            for (op, y, k, n) in cases {
+                write!(f, " | {} {} {} => ", op, y, k)?;
+                fmt_term(n, 1, f)?
+            }
+        }
+        CallCC(m, _) => {
+            write!(f, "callcc ")?;
+            fmt_term(m, 8, f)?
+        }
+    }
+    if parens {
+        write!(f, ")")?;
+    }
+    Ok(())
 }
 
 pub fn int(i: i32) -> Box<Term> {
     Box::new(Term::Int(i))
 }
 
+pub fn float(f: f64) -> Box<Term> {
+    Box::new(Term::Float(f))
+}
+
 pub fn bool(b: bool) -> Box<Term> {
     Box::new(Term::Bool(b))
 }
@@ -47,20 +257,20 @@ pub fn var(x: Name) -> Box<Term> {
     Box::new(Term::Var(x))
 }
 
-pub fn op1(op: Op1, m: Box<Term>) -> Box<Term> {
-    Box::new(Term::Op1(op, m))
+pub fn op1(op: Op1, m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op1(op, m, pos))
 }
 
-pub fn op2(op: Op2, m: Box<Term>, n: Box<Term>) -> Box<Term> {
-    Box::new(Term::Op2(op, m, n))
+pub fn op2(op: Op2, m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op2(op, m, n, pos))
 }
 
 pub fn fun(x: Name, y: Name, m: Box<Term>) -> Box<Term> {
     Box::new(Term::Fun(x, y, m))
 }
 
-pub fn app(m: Box<Term>, n: Box<Term>) -> Box<Term> {
-    Box::new(Term::App(m, n))
+pub fn app(m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::App(m, n, pos))
 }
 
 pub fn letin(x: Name, m: Box<Term>, n: Box<Term>) -> Box<Term> {
@@ -70,3 +280,205 @@ pub fn letin(x: Name, m: Box<Term>, n: Box<Term>) -> Box<Term> {
 pub fn ifte(m: Box<Term>, n1: Box<Term>, n2: Box<Term>) -> Box<Term> {
     Box::new(Term::Ifte(m, n1, n2))
 }
+
+pub fn raise(m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Raise(m, pos))
+}
+
+pub fn trywith(m: Box<Term>, x: Name, n: Box<Term>) -> Box<Term> {
+    Box::new(Term::Try(m, x, n))
+}
+
+pub fn effect(op: Name, m: Box<Term>) -> Box<Term> {
+    Box::new(Term::Effect(op, m))
+}
+
+pub fn perform(op: Name, m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Perform(op, m, pos))
+}
+
+pub fn handle(m: Box<Term>, x: Name, n: Box<Term>, cases: Vec<Case>) -> Box<Term> {
+    Box::new(Term::Handle(m, x, n, cases))
+}
+
+pub fn callcc(m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::CallCC(m, pos))
+}
+
+pub fn free_vars(m0: &Term) -> HashSet<Name> {
+    use Term::*;
+    let mut fvs = HashSet::default();
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => (),
+        Var(x) => {
+            fvs.insert(x.clone());
+        }
+        Op1(_, m, _) | Raise(m, _) | Effect(_, m) | Perform(_, m, _) | CallCC(m, _) => {
+            fvs = free_vars(m);
+        }
+        Op2(_, m, n, _) | App(m, n, _) => {
+            fvs = free_vars(m);
+            fvs.extend(free_vars(n));
+        }
+        Fun(f, x, m) => {
+            fvs = free_vars(m);
+            fvs.remove(f);
+            fvs.remove(x);
+        }
+        LetIn(x, m, n) | Try(m, x, n) => {
+            fvs = free_vars(n);
+            fvs.remove(x);
+            fvs.extend(free_vars(m));
+        }
+        Ifte(m, n1, n2) => {
+            fvs = free_vars(m);
+            fvs.extend(free_vars(n1));
+            fvs.extend(free_vars(n2));
+        }
+        Handle(m, x, n, cases) => {
+            fvs = free_vars(n);
+            fvs.remove(x);
+            for (_, y, k, n) in cases {
+                let mut fvs0 = free_vars(n);
+                fvs0.remove(y);
+                fvs0.remove(k);
+                fvs.extend(fvs0);
+            }
+            fvs.extend(free_vars(m));
+        }
+    }
+    fvs
+}
+
+// m[x := n], binders of m that would capture a free variable of n are
+// renamed to fresh names
+pub fn substitute(m: &Term, x: &Name, n: &Term) -> Box<Term> {
+    subst(m, x, n, &free_vars(n))
+}
+
+fn subst(m0: &Term, x: &Name, n: &Term, fvs: &HashSet<Name>) -> Box<Term> {
+    use Term::*;
+    // ys are bound in m, returns them renamed where needed and m with x replaced
+    let bind = |ys: &[&Name], m: &Term| -> (Vec<Name>, Box<Term>) {
+        let mut ys: Vec<Name> = ys.iter().map(|&y| y.clone()).collect();
+        if ys.contains(x) {
+            return (ys, Box::new(m.clone()));
+        }
+        let mut renamed = None;
+        for y in ys.iter_mut() {
+            if fvs.contains(y) {
+                let z = y.fresh();
+                renamed = Some(substitute(
+                    renamed.as_deref().unwrap_or(m),
+                    y,
+                    &Var(z.clone()),
+                ));
+                *y = z;
+            }
+        }
+        (ys, subst(renamed.as_deref().unwrap_or(m), x, n, fvs))
+    };
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => Box::new(m0.clone()),
+        Var(y) if y == x => Box::new(n.clone()),
+        Var(_) => Box::new(m0.clone()),
+        Op1(op, m, pos) => op1(*op, subst(m, x, n, fvs), *pos),
+        Op2(op, m1, m2, pos) => op2(*op, subst(m1, x, n, fvs), subst(m2, x, n, fvs), *pos),
+        Fun(f, y, m) => {
+            let (fy, m) = bind(&[f, y], m);
+            fun(fy[0].clone(), fy[1].clone(), m)
+        }
+        App(m1, m2, pos) => app(subst(m1, x, n, fvs), subst(m2, x, n, fvs), *pos),
+        LetIn(y, m1, m2) => {
+            let m1 = subst(m1, x, n, fvs);
+            let (y, m2) = bind(&[y], m2);
+            letin(y[0].clone(), m1, m2)
+        }
+        Ifte(m, n1, n2) => ifte(
+            subst(m, x, n, fvs),
+            subst(n1, x, n, fvs),
+            subst(n2, x, n, fvs),
+        ),
+        Raise(m, pos) => raise(subst(m, x, n, fvs), *pos),
+        Try(m1, y, m2) => {
+            let m1 = subst(m1, x, n, fvs);
+            let (y, m2) = bind(&[y], m2);
+            trywith(m1, y[0].clone(), m2)
+        }
+        Effect(op, _) if op == x => Box::new(m0.clone()),
+        Effect(op, m) => effect(op.clone(), subst(m, x, n, fvs)),
+        Perform(op, m, pos) => perform(op.clone(), subst(m, x, n, fvs), *pos),
+        Handle(m, y, m1, cases) => {
+            let m = subst(m, x, n, fvs);
+            let (y, m1) = bind(&[y], m1);
+            let cases = cases
+                .iter()
+                .map(|(op, y, k, m)| {
+                    let (yk, m) = bind(&[y, k], m);
+                    (op.clone(), yk[0].clone(), yk[1].clone(), m)
+                })
+                .collect();
+            handle(m, y[0].clone(), m1, cases)
+        }
+        CallCC(m, pos) => callcc(subst(m, x, n, fvs), *pos),
+    }
+}
+
+// equality up to the names of bound variables, positions are ignored
+pub fn alpha_eq(a: &Term, b: &Term) -> bool {
+    alpha(a, b, &mut vec![])
+}
+
+fn same(x: &Name, y: &Name, env: &[(Name, Name)]) -> bool {
+    for (p, q) in env.iter().rev() {
+        if p == x || q == y {
+            return p == x && q == y;
+        }
+    }
+    x == y
+}
+
+fn under(env: &mut Vec<(Name, Name)>, binders: &[(&Name, &Name)], a: &Term, b: &Term) -> bool {
+    let len = env.len();
+    env.extend(binders.iter().map(|(x, y)| ((*x).clone(), (*y).clone())));
+    let result = alpha(a, b, env);
+    env.truncate(len);
+    result
+}
+
+fn alpha(a: &Term, b: &Term, env: &mut Vec<(Name, Name)>) -> bool {
+    use Term::*;
+    match (a, b) {
+        (Int(i), Int(j)) => i == j,
+        (Float(f), Float(g)) => f.to_bits() == g.to_bits(),
+        (Bool(p), Bool(q)) => p == q,
+        (Var(x), Var(y)) => same(x, y, env),
+        (Op1(op, m, _), Op1(op0, n, _)) => op == op0 && alpha(m, n, env),
+        (Op2(op, m1, m2, _), Op2(op0, n1, n2, _)) => {
+            op == op0 && alpha(m1, n1, env) && alpha(m2, n2, env)
+        }
+        (Fun(f, x, m), Fun(g, y, n)) => under(env, &[(f, g), (x, y)], m, n),
+        (App(m1, m2, _), App(n1, n2, _)) => alpha(m1, n1, env) && alpha(m2, n2, env),
+        (LetIn(x, m1, m2), LetIn(y, n1, n2)) | (Try(m1, x, m2), Try(n1, y, n2)) => {
+            alpha(m1, n1, env) && under(env, &[(x, y)], m2, n2)
+        }
+        (Ifte(m, m1, m2), Ifte(n, n1, n2)) => {
+            alpha(m, n, env) && alpha(m1, n1, env) && alpha(m2, n2, env)
+        }
+        (Raise(m, _), Raise(n, _)) | (CallCC(m, _), CallCC(n, _)) => alpha(m, n, env),
+        (Effect(op, m), Effect(op0, n)) => under(env, &[(op, op0)], m, n),
+        (Perform(op, m, _), Perform(op0, n, _)) => same(op, op0, env) && alpha(m, n, env),
+        (Handle(m, x, m1, cases), Handle(n, y, n1, cases0)) => {
+            alpha(m, n, env)
+                && under(env, &[(x, y)], m1, n1)
+                && cases.len() == cases0.len()
+                && cases
+                    .iter()
+                    .zip(cases0)
+                    .all(|((op, x, k, m), (op0, y, l, n))| {
+                        same(op, op0, env) && under(env, &[(x, y), (k, l)], m, n)
+                    })
+        }
+        (_, _) => false,
+    }
+}
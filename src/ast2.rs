@@ -1,28 +1,11 @@
-use crate::names::*;
+use crate::{
+    ast1::{Op1, Op2},
+    error::{Exn, Pos},
+    names::*,
+    ops::{Prim, View, eval_op1, eval_op2, short_circuit},
+};
 use derivative::Derivative;
-use std::rc::*;
-
-#[derive(Debug, Clone, Copy)]
-pub enum Op1 {
-    Neg,
-    Not,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum Op2 {
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Lte,
-    Gte,
-    Lt,
-    Gt,
-    Eq,
-    Neq,
-    And,
-    Or,
-}
+use std::{cell::RefCell, rc::*};
 
 pub type Binder1<'a> = Rc<dyn Fn(Term<'a>) -> Term<'a> + 'a>;
 pub type Binder2<'a> = Rc<dyn Fn(Term<'a>, Term<'a>) -> Term<'a> + 'a>;
@@ -32,20 +15,28 @@ pub type Term<'a> = Rc<TermNode<'a>>;
 #[derivative(Debug)]
 pub enum TermNode<'a> {
     Int(i32),
+    Float(f64),
     Bool(bool),
     Var(Rc<Name>),
-    Op1(Op1, Term<'a>),
-    Op2(Op2, Term<'a>, Term<'a>),
+    Op1(Op1, Term<'a>, Pos),
+    Op2(Op2, Term<'a>, Term<'a>, Pos),
     Fun(#[derivative(Debug = "ignore")] Binder2<'a>),
     App(Term<'a>, Term<'a>),
     LetIn(Term<'a>, #[derivative(Debug = "ignore")] Binder1<'a>),
     Ifte(Term<'a>, Term<'a>, Term<'a>),
+    Raise(Term<'a>, Pos),
+    Try(Term<'a>, #[derivative(Debug = "ignore")] Binder1<'a>),
+    Ref(Rc<RefCell<Term<'a>>>),
 }
 
 pub fn int<'a>(i: i32) -> Term<'a> {
     Rc::new(TermNode::Int(i))
 }
 
+pub fn float<'a>(f: f64) -> Term<'a> {
+    Rc::new(TermNode::Float(f))
+}
+
 pub fn bool<'a>(b: bool) -> Term<'a> {
     Rc::new(TermNode::Bool(b))
 }
@@ -54,12 +45,12 @@ pub fn var<'a>(x: Rc<Name>) -> Term<'a> {
     Rc::new(TermNode::Var(x))
 }
 
-pub fn op1<'a>(op: Op1, m: Term<'a>) -> Term<'a> {
-    Rc::new(TermNode::Op1(op, m))
+pub fn op1<'a>(op: Op1, m: Term<'a>, pos: Pos) -> Term<'a> {
+    Rc::new(TermNode::Op1(op, m, pos))
 }
 
-pub fn op2<'a>(op: Op2, m: Term<'a>, n: Term<'a>) -> Term<'a> {
-    Rc::new(TermNode::Op2(op, m, n))
+pub fn op2<'a>(op: Op2, m: Term<'a>, n: Term<'a>, pos: Pos) -> Term<'a> {
+    Rc::new(TermNode::Op2(op, m, n, pos))
 }
 
 pub fn fun<'a>(bnd: Binder2<'a>) -> Term<'a> {
@@ -78,71 +69,92 @@ pub fn ifte<'a>(m: Term<'a>, n1: Term<'a>, n2: Term<'a>) -> Term<'a> {
     Rc::new(TermNode::Ifte(m, n1, n2))
 }
 
-pub fn reduce<'a>(m0: Term<'a>) -> Term<'a> {
+pub fn raise<'a>(m: Term<'a>, pos: Pos) -> Term<'a> {
+    Rc::new(TermNode::Raise(m, pos))
+}
+
+pub fn trywith<'a>(m: Term<'a>, bnd: Binder1<'a>) -> Term<'a> {
+    Rc::new(TermNode::Try(m, bnd))
+}
+
+pub fn reduce<'a>(m0: Term<'a>) -> Result<Term<'a>, Exn<Term<'a>>> {
     use TermNode::*;
     match &*m0 {
-        Int(_) => m0.clone(),
-        Bool(_) => m0.clone(),
-        Var(_) => m0.clone(),
-        Op1(op1, m) => {
-            let m = reduce(m0.clone());
-            reduce_op1(&op1, m)
+        Int(_) => Ok(m0.clone()),
+        Float(_) => Ok(m0.clone()),
+        Ref(_) => Ok(m0.clone()),
+        Bool(_) => Ok(m0.clone()),
+        Var(_) => Ok(m0.clone()),
+        Op1(op1, m, pos) => {
+            let m = reduce(m.clone())?;
+            Ok(eval_op1(op1, &m, *pos)?)
         }
-        Op2(op2, m, n) => {
-            let m = reduce(m.clone());
-            let n = reduce(n.clone());
-            reduce_op2(&op2, m, n)
+        Op2(op2, m, n, pos) => {
+            let m = reduce(m.clone())?;
+            if let Some(v) = short_circuit(op2, &m) {
+                return Ok(v);
+            }
+            let n = reduce(n.clone())?;
+            Ok(eval_op2(op2, &m, &n, *pos)?)
         }
-        Fun(_) => m0.clone(),
+        Fun(_) => Ok(m0.clone()),
         App(m, n) => {
-            let m1 = reduce(m.clone());
-            let n = reduce(n.clone());
+            let m1 = reduce(m.clone())?;
+            let n = reduce(n.clone())?;
             match &*m1 {
                 Fun(bnd) => reduce(bnd(m1.clone(), n)),
                 _ => panic!("eval_App"),
             }
         }
         LetIn(m, bnd) => {
-            let m = reduce(m.clone());
+            let m = reduce(m.clone())?;
             reduce(bnd(m))
         }
         Ifte(m, n1, n2) => {
-            let m = reduce(m.clone());
+            let m = reduce(m.clone())?;
             match &*m {
                 Bool(true) => reduce(n1.clone()),
                 Bool(false) => reduce(n2.clone()),
                 _ => panic!("eval_Ifte"),
             }
         }
+        Raise(m, pos) => {
+            let m = reduce(m.clone())?;
+            Err(Exn::Raise(m, *pos))
+        }
+        Try(m, bnd) => match reduce(m.clone()) {
+            Ok(m) => Ok(m),
+            Err(Exn::Raise(v, _)) => reduce(bnd(v)),
+            Err(Exn::Error(e)) => reduce(bnd(int(e.code()))),
+        },
     }
 }
 
-fn reduce_op1<'a>(op: &Op1, m: Term<'a>) -> Term<'a> {
-    use self::Op1::*;
-    use TermNode::*;
-    match (op, &*m) {
-        (Not, Bool(b)) => bool(!b),
-        (Neg, Int(i)) => int(-i),
-        (_, _) => panic!("eval_op1"),
+impl Prim for Term<'_> {
+    fn int(i: i32) -> Self {
+        int(i)
     }
-}
 
-fn reduce_op2<'a>(op: &Op2, m: Term<'a>, n: Term<'a>) -> Term<'a> {
-    use self::Op2::*;
-    use TermNode::*;
-    match (op, &*m, &*n) {
-        (Add, Int(i), Int(j)) => int(i + j),
-        (Sub, Int(i), Int(j)) => int(i - j),
-        (Mul, Int(i), Int(j)) => int(i * j),
-        (Div, Int(i), Int(j)) => int(i / j),
-        (Lte, Int(i), Int(j)) => bool(i <= j),
-        (Gte, Int(i), Int(j)) => bool(i >= j),
-        (Lt, Int(i), Int(j)) => bool(i < j),
-        (Gt, Int(i), Int(j)) => bool(i > j),
-        (Eq, Int(i), Int(j)) => bool(i == j),
-        (Neq, Int(i), Int(j)) => bool(i != j),
-        (And, Bool(i), Bool(j)) => bool(*i && *j),
-        (Or, Bool(i), Bool(j)) => bool(*i || *j),
-        (_, _, _) => panic!("eval_op2"),
+    fn float(f: f64) -> Self {
+        float(f)
+    }
+
+    fn bool(b: bool) -> Self {
+        bool(b)
+    }
+
+    fn reference(r: Rc<RefCell<Self>>) -> Self {
+        Rc::new(TermNode::Ref(r))
+    }
+
+    fn view(&self) -> View<'_, Self> {
+        match &**self {
+            TermNode::Int(i) => View::Int(*i),
+            TermNode::Float(f) => View::Float(*f),
+            TermNode::Bool(b) => View::Bool(*b),
+            TermNode::Ref(r) => View::Ref(r),
+            TermNode::Fun(_) => View::Fun,
+            _ => View::Other,
+        }
     }
 }
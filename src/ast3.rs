@@ -0,0 +1,322 @@
+use crate::{
+    ast1::{Op1, Op2},
+    error::{Exn, Pos},
+    names::*,
+    ops::{Prim, View, eval_op1, eval_op2, short_circuit},
+};
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Display},
+    rc::Rc,
+};
+
+// a closure converted program, no function refers to a variable bound
+// outside of its own body except through the closure it is called with
+pub struct Prog {
+    pub funs: Vec<Def>,
+    pub main: Box<Term>,
+}
+
+// the code of a function, clo is bound to the closure the function was
+// called through and param to the argument
+pub struct Def {
+    pub name: Name,
+    pub clo: Name,
+    pub param: Name,
+    pub body: Box<Term>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Var(Name),
+    // the i-th captured variable of a closure
+    Field(Name, usize),
+    Op1(Op1, Box<Term>, Pos),
+    Op2(Op2, Box<Term>, Box<Term>, Pos),
+    // a code pointer into Prog::funs paired with the values it captures
+    Clo(usize, Vec<Term>),
+    App(Box<Term>, Box<Term>, Pos),
+    LetIn(Name, Box<Term>, Box<Term>),
+    Ifte(Box<Term>, Box<Term>, Box<Term>),
+    Raise(Box<Term>, Pos),
+    Try(Box<Term>, Name, Box<Term>),
+}
+
+pub fn int(i: i32) -> Box<Term> {
+    Box::new(Term::Int(i))
+}
+
+pub fn float(f: f64) -> Box<Term> {
+    Box::new(Term::Float(f))
+}
+
+pub fn bool(b: bool) -> Box<Term> {
+    Box::new(Term::Bool(b))
+}
+
+pub fn var(x: Name) -> Box<Term> {
+    Box::new(Term::Var(x))
+}
+
+pub fn field(clo: Name, i: usize) -> Box<Term> {
+    Box::new(Term::Field(clo, i))
+}
+
+pub fn op1(op: Op1, m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op1(op, m, pos))
+}
+
+pub fn op2(op: Op2, m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Op2(op, m, n, pos))
+}
+
+pub fn clo(code: usize, env: Vec<Term>) -> Box<Term> {
+    Box::new(Term::Clo(code, env))
+}
+
+pub fn app(m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::App(m, n, pos))
+}
+
+pub fn letin(x: Name, m: Box<Term>, n: Box<Term>) -> Box<Term> {
+    Box::new(Term::LetIn(x, m, n))
+}
+
+pub fn ifte(m: Box<Term>, n1: Box<Term>, n2: Box<Term>) -> Box<Term> {
+    Box::new(Term::Ifte(m, n1, n2))
+}
+
+pub fn raise(m: Box<Term>, pos: Pos) -> Box<Term> {
+    Box::new(Term::Raise(m, pos))
+}
+
+pub fn trywith(m: Box<Term>, x: Name, n: Box<Term>) -> Box<Term> {
+    Box::new(Term::Try(m, x, n))
+}
+
+impl Display for Prog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for def in &self.funs {
+            write!(f, "fun {} {} {} := ", def.name, def.clo, def.param)?;
+            fmt_term(self, &def.body, false, f)?;
+            writeln!(f)?;
+        }
+        fmt_term(self, &self.main, false, f)
+    }
+}
+
+// fields and closure literals have no source syntax, so instead of
+// following precedence every compound operand is parenthesized
+fn fmt_term(prog: &Prog, m0: &Term, operand: bool, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Term::*;
+    let atom = match m0 {
+        Int(i) => *i >= 0,
+        Float(x) => x.is_sign_positive(),
+        Bool(_) | Var(_) | Field(..) | Clo(..) => true,
+        _ => false,
+    };
+    if operand && !atom {
+        write!(f, "(")?;
+    }
+    match m0 {
+        Int(i) => write!(f, "{}", i)?,
+        Float(x) => write!(f, "{:?}", x)?,
+        Bool(b) => write!(f, "{}", b)?,
+        Var(x) => write!(f, "{}", x)?,
+        Field(c, i) => write!(f, "{}.{}", c, i)?,
+        Op1(op, m, _) => {
+            write!(f, "{}", op)?;
+            fmt_term(prog, m, true, f)?
+        }
+        Op2(op, m, n, _) => {
+            fmt_term(prog, m, true, f)?;
+            write!(f, " {} ", op)?;
+            fmt_term(prog, n, true, f)?
+        }
+        Clo(code, env) => {
+            write!(f, "<{}", prog.funs[*code].name)?;
+            for m in env {
+                write!(f, " ")?;
+                fmt_term(prog, m, true, f)?
+            }
+            write!(f, ">")?
+        }
+        App(m, n, _) => {
+            fmt_term(prog, m, true, f)?;
+            write!(f, " ")?;
+            fmt_term(prog, n, true, f)?
+        }
+        LetIn(x, m, n) => {
+            write!(f, "let {} := ", x)?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " in ")?;
+            fmt_term(prog, n, false, f)?
+        }
+        Ifte(m, n1, n2) => {
+            write!(f, "if ")?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " then ")?;
+            fmt_term(prog, n1, false, f)?;
+            write!(f, " else ")?;
+            fmt_term(prog, n2, false, f)?
+        }
+        Raise(m, _) => {
+            write!(f, "raise ")?;
+            fmt_term(prog, m, true, f)?
+        }
+        Try(m, x, n) => {
+            write!(f, "try ")?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " with {} => ", x)?;
+            fmt_term(prog, n, false, f)?
+        }
+    }
+    if operand && !atom {
+        write!(f, ")")?;
+    }
+    Ok(())
+}
+
+pub struct Closure {
+    pub code: usize,
+    pub env: Vec<Value>,
+}
+
+impl Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Closure({})", self.code)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Clo(Rc<Closure>),
+    Ref(Rc<RefCell<Value>>),
+}
+
+pub fn run(prog: &Prog) -> Result<Value, Exn<Value>> {
+    eval(prog, &mut vec![], &prog.main)
+}
+
+// the variables bound in the current call, innermost last
+type Locals = Vec<(Name, Value)>;
+
+fn get(locals: &Locals, x: &Name) -> Value {
+    match locals.iter().rev().find(|(y, _)| y == x) {
+        Some((_, v)) => v.clone(),
+        None => panic!("cannot find({:?})", x),
+    }
+}
+
+fn bind(
+    prog: &Prog,
+    locals: &mut Locals,
+    x: &Name,
+    v: Value,
+    m: &Term,
+) -> Result<Value, Exn<Value>> {
+    locals.push((x.clone(), v));
+    let result = eval(prog, locals, m);
+    locals.pop();
+    result
+}
+
+fn eval(prog: &Prog, locals: &mut Locals, m0: &Term) -> Result<Value, Exn<Value>> {
+    use Term::*;
+    match m0 {
+        Int(i) => Ok(Value::Int(*i)),
+        Float(f) => Ok(Value::Float(*f)),
+        Bool(b) => Ok(Value::Bool(*b)),
+        Var(x) => Ok(get(locals, x)),
+        Field(c, i) => match get(locals, c) {
+            Value::Clo(clo) => Ok(clo.env[*i].clone()),
+            v => panic!("eval_Field({:?})", v),
+        },
+        Op1(op1, m, pos) => {
+            let m = eval(prog, locals, m)?;
+            Ok(eval_op1(op1, &m, *pos)?)
+        }
+        Op2(op2, m, n, pos) => {
+            let m = eval(prog, locals, m)?;
+            if let Some(v) = short_circuit(op2, &m) {
+                return Ok(v);
+            }
+            let n = eval(prog, locals, n)?;
+            Ok(eval_op2(op2, &m, &n, *pos)?)
+        }
+        Clo(code, env) => {
+            let env = env
+                .iter()
+                .map(|m| eval(prog, locals, m))
+                .collect::<Result<_, _>>()?;
+            Ok(Value::Clo(Rc::new(Closure { code: *code, env })))
+        }
+        App(m, n, _) => {
+            let m = eval(prog, locals, m)?;
+            let n = eval(prog, locals, n)?;
+            match &m {
+                Value::Clo(clo) => {
+                    let def = &prog.funs[clo.code];
+                    let mut locals = vec![(def.clo.clone(), m.clone()), (def.param.clone(), n)];
+                    eval(prog, &mut locals, &def.body)
+                }
+                _ => panic!("eval_App({:?})", m0),
+            }
+        }
+        LetIn(x, m, n) => {
+            let m = eval(prog, locals, m)?;
+            bind(prog, locals, x, m, n)
+        }
+        Ifte(m, n1, n2) => match eval(prog, locals, m)? {
+            Value::Bool(true) => eval(prog, locals, n1),
+            Value::Bool(false) => eval(prog, locals, n2),
+            _ => panic!("eval_Ifte({:?})", m0),
+        },
+        Raise(m, pos) => {
+            let m = eval(prog, locals, m)?;
+            Err(Exn::Raise(m, *pos))
+        }
+        Try(m, x, n) => {
+            let v = match eval(prog, locals, m) {
+                Ok(v) => return Ok(v),
+                Err(Exn::Raise(v, _)) => v,
+                Err(Exn::Error(e)) => Value::Int(e.code()),
+            };
+            bind(prog, locals, x, v, n)
+        }
+    }
+}
+
+impl Prim for Value {
+    fn int(i: i32) -> Self {
+        Value::Int(i)
+    }
+
+    fn float(f: f64) -> Self {
+        Value::Float(f)
+    }
+
+    fn bool(b: bool) -> Self {
+        Value::Bool(b)
+    }
+
+    fn reference(r: Rc<RefCell<Self>>) -> Self {
+        Value::Ref(r)
+    }
+
+    fn view(&self) -> View<'_, Self> {
+        match self {
+            Value::Int(i) => View::Int(*i),
+            Value::Float(f) => View::Float(*f),
+            Value::Bool(b) => View::Bool(*b),
+            Value::Ref(r) => View::Ref(r),
+            Value::Clo(_) => View::Fun,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    ast1::{Op1, Op2},
+    error::Pos,
+    names::*,
+};
+use std::fmt::{self, Display};
+
+// a-normal form, the operands of every operation are atoms and each
+// intermediate result is bound by a let, in evaluation order
+#[derive(Debug, Clone)]
+pub enum Atom {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Var(Name),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Atom(Atom),
+    // never && or ||, trans14 turns them into an if so that the right
+    // operand is only bound on the branch that evaluates it
+    Op1(Op1, Atom, Pos),
+    Op2(Op2, Atom, Atom, Pos),
+    Fun(Name, Name, Box<Term>),
+    App(Atom, Atom, Pos),
+    Ifte(Atom, Box<Term>, Box<Term>),
+    Raise(Atom, Pos),
+    Try(Box<Term>, Name, Box<Term>),
+    Effect(Name, Box<Term>),
+    Perform(Name, Atom, Pos),
+    Handle(Box<Term>, Name, Box<Term>, Vec<Case>),
+    CallCC(Atom, Pos),
+}
+
+pub type Case = (Name, Name, Name, Box<Term>);
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Let(Name, Expr, Box<Term>),
+    Tail(Expr),
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Atom::*;
+        match self {
+            Int(i) if *i < 0 => write!(f, "({})", i),
+            Int(i) => write!(f, "{}", i),
+            Float(x) if x.is_sign_negative() => write!(f, "({:?})", x),
+            Float(x) => write!(f, "{:?}", x),
+            Bool(b) => write!(f, "{}", b),
+            Var(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_term(self, 0, f)
+    }
+}
+
+// one binding per line, nested terms are indented by two
+fn fmt_term(m0: &Term, indent: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut m0 = m0;
+    while let Term::Let(x, e, m) = m0 {
+        match e {
+            Expr::Fun(g, y, m) if g == x => {
+                write!(f, "let rec {} {} :=", g, y)?;
+                fmt_block(m, indent, f)?
+            }
+            _ => {
+                write!(f, "let {} := ", x)?;
+                fmt_expr(e, indent, f)?;
+                write!(f, " ")?
+            }
+        }
+        write!(f, "in\n{:indent$}", "")?;
+        m0 = m;
+    }
+    match m0 {
+        Term::Tail(e) => fmt_expr(e, indent, f),
+        Term::Let(..) => unreachable!(),
+    }
+}
+
+fn fmt_block(m: &Term, indent: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "\n{:indent$}", "", indent = indent + 2)?;
+    fmt_term(m, indent + 2, f)?;
+    write!(f, "\n{:indent$}", "")
+}
+
+fn fmt_expr(e: &Expr, indent: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Expr::*;
+    match e {
+        Atom(a) => write!(f, "{}", a),
+        Op1(op, a, _) => write!(f, "{}{}", op, a),
+        Op2(op, a, b, _) => write!(f, "{} {} {}", a, op, b),
+        Fun(g, x, m) if g.to_string().is_empty() => {
+            write!(f, "(fun {} =>", x)?;
+            fmt_block(m, indent, f)?;
+            write!(f, ")")
+        }
+        Fun(g, x, m) => {
+            write!(f, "(let rec {} {} :=", g, x)?;
+            fmt_block(m, indent, f)?;
+            write!(f, "in {})", g)
+        }
+        App(a, b, _) => write!(f, "{} {}", a, b),
+        Ifte(a, m1, m2) => {
+            write!(f, "if {} then", a)?;
+            fmt_block(m1, indent, f)?;
+            write!(f, "else")?;
+            fmt_block(m2, indent, f)?;
+            write!(f, "end")
+        }
+        Raise(a, _) => write!(f, "raise {}", a),
+        Try(m, x, n) => {
+            write!(f, "try")?;
+            fmt_block(m, indent, f)?;
+            write!(f, "with {} =>", x)?;
+            fmt_block(n, indent, f)?;
+            write!(f, "end")
+        }
+        Effect(op, m) => {
+            write!(f, "effect {} in", op)?;
+            fmt_block(m, indent, f)?;
+            write!(f, "end")
+        }
+        Perform(op, a, _) => write!(f, "perform {} {}", op, a),
+        Handle(m, x, n, cases) => {
+            write!(f, "handle")?;
+            fmt_block(m, indent, f)?;
+            write!(f, "with | return {} =>", x)?;
+            fmt_block(n, indent, f)?;
+            for (op, y, k, n) in cases {
+                write!(f, "| {} {} {} =>", op, y, k)?;
+                fmt_block(n, indent, f)?;
+            }
+            write!(f, "end")
+        }
+        CallCC(a, _) => write!(f, "callcc {}", a),
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    ast1::{Op1, Op2},
+    error::{Error, Exn, Pos},
+    names::*,
+    ops::{Prim, View, eval_op1, eval_op2},
+};
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Display},
+    rc::Rc,
+};
+
+// continuation passing style, every call is a tail call and is passed
+// the continuation its result goes to
+#[derive(Debug, Clone)]
+pub enum Atom {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Var(Name),
+    // never && or ||, trans15 branches on the left operand and passes
+    // either outcome to one joined continuation
+    Op1(Op1, Box<Atom>, Pos),
+    Op2(Op2, Box<Atom>, Box<Atom>, Pos),
+    // function, parameter, continuation parameter and body
+    Fun(Name, Name, Name, Box<Term>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Cont {
+    Var(Name),
+    Fun(Name, Box<Term>),
+    // the end of the program
+    Halt,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Let(Name, Atom, Box<Term>),
+    // a continuation that is jumped to from several places
+    LetCont(Name, Name, Box<Term>, Box<Term>),
+    App(Atom, Atom, Cont, Pos),
+    Ret(Cont, Atom),
+    Ifte(Atom, Box<Term>, Box<Term>),
+    Raise(Atom, Pos),
+    // installs the handler for the extent of the body, the body leaves
+    // through Uncatch, which removes it again
+    Catch(Name, Box<Term>, Box<Term>),
+    Uncatch(Cont, Atom),
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Term::*;
+        match self {
+            Let(x, Atom::Fun(g, y, k, m), n) if g == x => {
+                write!(f, "let rec {} {} {} := {} in {}", g, y, k, m, n)
+            }
+            Let(x, a, n) => write!(f, "let {} := {} in {}", x, a, n),
+            LetCont(j, x, m, n) => write!(f, "let cont {} {} := {} in {}", j, x, m, n),
+            App(a, b, c, _) => {
+                fmt_operand(a, f)?;
+                write!(f, " ")?;
+                fmt_operand(b, f)?;
+                write!(f, " {}", c)
+            }
+            Ret(Cont::Fun(x, m), a) => {
+                write!(f, "(fun {} => {}) ", x, m)?;
+                fmt_operand(a, f)
+            }
+            Ret(c, a) => {
+                write!(f, "{} ", c)?;
+                fmt_operand(a, f)
+            }
+            Uncatch(c, a) => {
+                write!(f, "uncatch {} ", c)?;
+                fmt_operand(a, f)
+            }
+            Ifte(a, m1, m2) => write!(f, "if {} then {} else {}", a, m1, m2),
+            Raise(a, _) => {
+                write!(f, "raise ")?;
+                fmt_operand(a, f)
+            }
+            Catch(x, m, n) => write!(f, "catch (fun {} => {}) in {}", x, m, n),
+        }
+    }
+}
+
+impl Display for Cont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cont::Var(k) => write!(f, "{}", k),
+            Cont::Fun(x, m) => write!(f, "(fun {} => {})", x, m),
+            Cont::Halt => write!(f, "halt"),
+        }
+    }
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Atom::*;
+        match self {
+            Int(i) => write!(f, "{}", i),
+            Float(x) => write!(f, "{:?}", x),
+            Bool(b) => write!(f, "{}", b),
+            Var(x) => write!(f, "{}", x),
+            Op1(op, a, _) => {
+                write!(f, "{}", op)?;
+                fmt_operand(a, f)
+            }
+            Op2(op, a, b, _) => {
+                fmt_operand(a, f)?;
+                write!(f, " {} ", op)?;
+                fmt_operand(b, f)
+            }
+            Fun(g, x, k, m) if g.to_string().is_empty() => {
+                write!(f, "fun {} {} => {}", x, k, m)
+            }
+            Fun(g, x, k, m) => write!(f, "let rec {} {} {} := {} in {}", g, x, k, m, g),
+        }
+    }
+}
+
+// calls take their continuation as an extra operand, which leaves no
+// room for precedence, so every compound atom is parenthesized
+fn fmt_operand(a: &Atom, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Atom::*;
+    match a {
+        Int(i) if *i >= 0 => write!(f, "{}", a),
+        Float(x) if x.is_sign_positive() => write!(f, "{}", a),
+        Bool(_) | Var(_) => write!(f, "{}", a),
+        _ => write!(f, "({})", a),
+    }
+}
+
+pub struct Closure<'a> {
+    env: Env<'a>,
+    func: &'a Name,
+    param: &'a Name,
+    cont: &'a Name,
+    body: &'a Term,
+}
+
+impl Debug for Closure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Closure({:?}, {:?})", self.func, self.param)
+    }
+}
+
+pub struct ContClosure<'a> {
+    env: Env<'a>,
+    param: &'a Name,
+    body: &'a Term,
+}
+
+impl Debug for ContClosure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cont({:?})", self.param)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Clo(Rc<Closure<'a>>),
+    Ref(Rc<RefCell<Value<'a>>>),
+    Cont(Rc<ContClosure<'a>>),
+    Halt,
+}
+
+// bindings innermost first, shared between closures
+#[derive(Clone, Default)]
+pub struct Env<'a>(Option<Rc<(Name, Value<'a>, Env<'a>)>>);
+
+impl<'a> Env<'a> {
+    fn get(&self, x: &Name) -> Value<'a> {
+        let mut env = &self.0;
+        while let Some(bind) = env {
+            if bind.0 == *x {
+                return bind.1.clone();
+            }
+            env = &bind.2.0;
+        }
+        panic!("cannot find({:?})", x)
+    }
+
+    fn bind(&self, x: &Name, v: Value<'a>) -> Env<'a> {
+        Env(Some(Rc::new((x.clone(), v, self.clone()))))
+    }
+}
+
+pub struct Machine<'a> {
+    pub ctrl: &'a Term,
+    pub env: Env<'a>,
+    // the handlers of the catches in progress, innermost last
+    pub handlers: Vec<ContClosure<'a>>,
+    pub steps: usize,
+}
+
+pub fn run<'a>(m: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
+    let mut machine = Machine::new(m);
+    loop {
+        if let Some(result) = machine.step() {
+            return result;
+        }
+    }
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(m: &'a Term) -> Self {
+        Machine {
+            ctrl: m,
+            env: Env::default(),
+            handlers: vec![],
+            steps: 0,
+        }
+    }
+
+    fn atom(&self, a: &'a Atom) -> Result<Value<'a>, Error> {
+        use Atom::*;
+        match a {
+            Int(i) => Ok(Value::Int(*i)),
+            Float(f) => Ok(Value::Float(*f)),
+            Bool(b) => Ok(Value::Bool(*b)),
+            Var(x) => Ok(self.env.get(x)),
+            Op1(op, a, pos) => Ok(eval_op1(op, &self.atom(a)?, *pos)?),
+            Op2(op, a, b, pos) => {
+                let a = self.atom(a)?;
+                let b = self.atom(b)?;
+                eval_op2(op, &a, &b, *pos)
+            }
+            Fun(f, x, k, m) => Ok(Value::Clo(Rc::new(Closure {
+                env: self.env.clone(),
+                func: f,
+                param: x,
+                cont: k,
+                body: m,
+            }))),
+        }
+    }
+
+    fn cont(&self, c: &'a Cont) -> Value<'a> {
+        match c {
+            Cont::Var(k) => self.env.get(k),
+            Cont::Fun(x, m) => Value::Cont(Rc::new(ContClosure {
+                env: self.env.clone(),
+                param: x,
+                body: m,
+            })),
+            Cont::Halt => Value::Halt,
+        }
+    }
+
+    fn jump(&mut self, k: Value<'a>, v: Value<'a>) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        match k {
+            Value::Cont(k) => {
+                self.env = k.env.bind(k.param, v);
+                self.ctrl = k.body;
+                None
+            }
+            Value::Halt => Some(Ok(v)),
+            _ => panic!("jump({:?})", k),
+        }
+    }
+
+    // to the innermost handler, errors are passed as their code
+    fn throw(&mut self, exn: Exn<Value<'a>>) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        let Some(h) = self.handlers.pop() else {
+            return Some(Err(exn));
+        };
+        let v = match exn {
+            Exn::Raise(v, _) => v,
+            Exn::Error(e) => Value::Int(e.code()),
+        };
+        self.env = h.env.bind(h.param, v);
+        self.ctrl = h.body;
+        None
+    }
+
+    pub fn step(&mut self) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        use Term::*;
+        self.steps += 1;
+        match self.ctrl {
+            Let(x, a, m) => match self.atom(a) {
+                Ok(v) => {
+                    self.env = self.env.bind(x, v);
+                    self.ctrl = m;
+                    None
+                }
+                Err(e) => self.throw(e.into()),
+            },
+            LetCont(j, x, m, n) => {
+                let k = Value::Cont(Rc::new(ContClosure {
+                    env: self.env.clone(),
+                    param: x,
+                    body: m,
+                }));
+                self.env = self.env.bind(j, k);
+                self.ctrl = n;
+                None
+            }
+            App(a, b, c, _) => {
+                let (f, v) = match (self.atom(a), self.atom(b)) {
+                    (Ok(f), Ok(v)) => (f, v),
+                    (Err(e), _) | (_, Err(e)) => return self.throw(e.into()),
+                };
+                let k = self.cont(c);
+                match &f {
+                    Value::Clo(clo) => {
+                        self.env = clo
+                            .env
+                            .bind(clo.func, f.clone())
+                            .bind(clo.param, v)
+                            .bind(clo.cont, k);
+                        self.ctrl = clo.body;
+                        None
+                    }
+                    _ => panic!("eval_App({:?})", self.ctrl),
+                }
+            }
+            Ret(c, a) => match self.atom(a) {
+                Ok(v) => self.jump(self.cont(c), v),
+                Err(e) => self.throw(e.into()),
+            },
+            Ifte(a, m1, m2) => match self.atom(a) {
+                Ok(Value::Bool(true)) => {
+                    self.ctrl = m1;
+                    None
+                }
+                Ok(Value::Bool(false)) => {
+                    self.ctrl = m2;
+                    None
+                }
+                Ok(_) => panic!("eval_Ifte({:?})", self.ctrl),
+                Err(e) => self.throw(e.into()),
+            },
+            Raise(a, pos) => match self.atom(a) {
+                Ok(v) => self.throw(Exn::Raise(v, *pos)),
+                Err(e) => self.throw(e.into()),
+            },
+            Catch(x, m, n) => {
+                self.handlers.push(ContClosure {
+                    env: self.env.clone(),
+                    param: x,
+                    body: m,
+                });
+                self.ctrl = n;
+                None
+            }
+            Uncatch(c, a) => match self.atom(a) {
+                Ok(v) => {
+                    self.handlers.pop();
+                    self.jump(self.cont(c), v)
+                }
+                Err(e) => self.throw(e.into()),
+            },
+        }
+    }
+}
+
+impl Prim for Value<'_> {
+    fn int(i: i32) -> Self {
+        Value::Int(i)
+    }
+
+    fn float(f: f64) -> Self {
+        Value::Float(f)
+    }
+
+    fn bool(b: bool) -> Self {
+        Value::Bool(b)
+    }
+
+    fn reference(r: Rc<RefCell<Self>>) -> Self {
+        Value::Ref(r)
+    }
+
+    fn view(&self) -> View<'_, Self> {
+        match self {
+            Value::Int(i) => View::Int(*i),
+            Value::Float(f) => View::Float(*f),
+            Value::Bool(b) => View::Bool(*b),
+            Value::Ref(r) => View::Ref(r),
+            Value::Clo(_) | Value::Cont(_) | Value::Halt => View::Fun,
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    ast1::{Op1, Op2},
+    ast3::{Closure, Value},
+    error::{Exn, Pos},
+    names::*,
+    ops::{eval_op1, eval_op2, short_circuit},
+};
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+// a defunctionalized program, functions are values of one data type with
+// a constructor per fun of the source, and every application goes
+// through apply, which dispatches on the constructor
+pub struct Prog {
+    pub cases: Vec<Case>,
+    pub main: Box<Term>,
+}
+
+// apply (Con fields as func) param := body
+pub struct Case {
+    pub con: Name,
+    pub func: Name,
+    pub fields: Vec<Name>,
+    pub param: Name,
+    pub body: Box<Term>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Var(Name),
+    Op1(Op1, Box<Term>, Pos),
+    Op2(Op2, Box<Term>, Box<Term>, Pos),
+    // an index into Prog::cases and the variables stored in the value
+    Con(usize, Vec<Name>),
+    Apply(Box<Term>, Box<Term>, Pos),
+    LetIn(Name, Box<Term>, Box<Term>),
+    Ifte(Box<Term>, Box<Term>, Box<Term>),
+    Raise(Box<Term>, Pos),
+    Try(Box<Term>, Name, Box<Term>),
+}
+
+impl Display for Prog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for case in &self.cases {
+            write!(f, "apply ({}", case.con)?;
+            for x in &case.fields {
+                write!(f, " {}", x)?;
+            }
+            write!(f, " as {}) {} := ", case.func, case.param)?;
+            fmt_term(self, &case.body, false, f)?;
+            writeln!(f)?;
+        }
+        fmt_term(self, &self.main, false, f)
+    }
+}
+
+// constructors and apply are not source syntax, as an operand a
+// constructor with fields is parenthesized like any other compound term
+fn fmt_term(prog: &Prog, m0: &Term, operand: bool, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Term::*;
+    let atom = match m0 {
+        Int(i) => *i >= 0,
+        Float(x) => x.is_sign_positive(),
+        Con(_, xs) => xs.is_empty(),
+        Bool(_) | Var(_) => true,
+        _ => false,
+    };
+    if operand && !atom {
+        write!(f, "(")?;
+    }
+    match m0 {
+        Int(i) => write!(f, "{}", i)?,
+        Float(x) => write!(f, "{:?}", x)?,
+        Bool(b) => write!(f, "{}", b)?,
+        Var(x) => write!(f, "{}", x)?,
+        Op1(op, m, _) => {
+            write!(f, "{}", op)?;
+            fmt_term(prog, m, true, f)?
+        }
+        Op2(op, m, n, _) => {
+            fmt_term(prog, m, true, f)?;
+            write!(f, " {} ", op)?;
+            fmt_term(prog, n, true, f)?
+        }
+        Con(tag, xs) => {
+            write!(f, "{}", prog.cases[*tag].con)?;
+            for x in xs {
+                write!(f, " {}", x)?
+            }
+        }
+        Apply(m, n, _) => {
+            write!(f, "apply ")?;
+            fmt_term(prog, m, true, f)?;
+            write!(f, " ")?;
+            fmt_term(prog, n, true, f)?
+        }
+        LetIn(x, m, n) => {
+            write!(f, "let {} := ", x)?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " in ")?;
+            fmt_term(prog, n, false, f)?
+        }
+        Ifte(m, n1, n2) => {
+            write!(f, "if ")?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " then ")?;
+            fmt_term(prog, n1, false, f)?;
+            write!(f, " else ")?;
+            fmt_term(prog, n2, false, f)?
+        }
+        Raise(m, _) => {
+            write!(f, "raise ")?;
+            fmt_term(prog, m, true, f)?
+        }
+        Try(m, x, n) => {
+            write!(f, "try ")?;
+            fmt_term(prog, m, false, f)?;
+            write!(f, " with {} => ", x)?;
+            fmt_term(prog, n, false, f)?
+        }
+    }
+    if operand && !atom {
+        write!(f, ")")?;
+    }
+    Ok(())
+}
+
+impl Prog {
+    // values are plain data, a function prints as its constructor applied
+    // to the values of its fields
+    pub fn show(&self, v: &Value) -> String {
+        match v {
+            Value::Clo(clo) if clo.env.is_empty() => self.cases[clo.code].con.to_string(),
+            Value::Clo(clo) => {
+                let fields: Vec<_> = clo.env.iter().map(|v| self.show(v)).collect();
+                format!("{}({})", self.cases[clo.code].con, fields.join(", "))
+            }
+            Value::Ref(r) => format!("ref {}", self.show(&r.borrow())),
+            Value::Int(i) => i.to_string(),
+            Value::Float(f) => format!("{:?}", f),
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+// a constructor is kept as an ast3 closure, its code is the tag and its
+// environment the fields
+pub fn run(prog: &Prog) -> Result<Value, Exn<Value>> {
+    eval(prog, &mut vec![], &prog.main)
+}
+
+type Locals = Vec<(Name, Value)>;
+
+fn get(locals: &Locals, x: &Name) -> Value {
+    match locals.iter().rev().find(|(y, _)| y == x) {
+        Some((_, v)) => v.clone(),
+        None => panic!("cannot find({:?})", x),
+    }
+}
+
+fn bind(
+    prog: &Prog,
+    locals: &mut Locals,
+    x: &Name,
+    v: Value,
+    m: &Term,
+) -> Result<Value, Exn<Value>> {
+    locals.push((x.clone(), v));
+    let result = eval(prog, locals, m);
+    locals.pop();
+    result
+}
+
+fn apply(prog: &Prog, f: Value, v: Value) -> Result<Value, Exn<Value>> {
+    let Value::Clo(clo) = &f else {
+        panic!("apply({:?})", f)
+    };
+    let case = &prog.cases[clo.code];
+    let mut locals: Locals = case.fields.iter().cloned().zip(clo.env.clone()).collect();
+    locals.push((case.func.clone(), f.clone()));
+    locals.push((case.param.clone(), v));
+    eval(prog, &mut locals, &case.body)
+}
+
+fn eval(prog: &Prog, locals: &mut Locals, m0: &Term) -> Result<Value, Exn<Value>> {
+    use Term::*;
+    match m0 {
+        Int(i) => Ok(Value::Int(*i)),
+        Float(f) => Ok(Value::Float(*f)),
+        Bool(b) => Ok(Value::Bool(*b)),
+        Var(x) => Ok(get(locals, x)),
+        Op1(op1, m, pos) => {
+            let m = eval(prog, locals, m)?;
+            Ok(eval_op1(op1, &m, *pos)?)
+        }
+        Op2(op2, m, n, pos) => {
+            let m = eval(prog, locals, m)?;
+            if let Some(v) = short_circuit(op2, &m) {
+                return Ok(v);
+            }
+            let n = eval(prog, locals, n)?;
+            Ok(eval_op2(op2, &m, &n, *pos)?)
+        }
+        Con(tag, xs) => {
+            let env = xs.iter().map(|x| get(locals, x)).collect();
+            Ok(Value::Clo(Rc::new(Closure { code: *tag, env })))
+        }
+        Apply(m, n, _) => {
+            let m = eval(prog, locals, m)?;
+            let n = eval(prog, locals, n)?;
+            apply(prog, m, n)
+        }
+        LetIn(x, m, n) => {
+            let m = eval(prog, locals, m)?;
+            bind(prog, locals, x, m, n)
+        }
+        Ifte(m, n1, n2) => match eval(prog, locals, m)? {
+            Value::Bool(true) => eval(prog, locals, n1),
+            Value::Bool(false) => eval(prog, locals, n2),
+            _ => panic!("eval_Ifte({:?})", m0),
+        },
+        Raise(m, pos) => {
+            let m = eval(prog, locals, m)?;
+            Err(Exn::Raise(m, *pos))
+        }
+        Try(m, x, n) => {
+            let v = match eval(prog, locals, m) {
+                Ok(v) => return Ok(v),
+                Err(Exn::Raise(v, _)) => v,
+                Err(Exn::Error(e)) => Value::Int(e.code()),
+            };
+            bind(prog, locals, x, v, n)
+        }
+    }
+}
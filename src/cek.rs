@@ -0,0 +1,302 @@
+use crate::{
+    ast1::*,
+    error::{Error, Exn, Pos},
+    eval::{Closure, Value},
+    names::Name,
+    ops::{eval_op1, eval_op2, short_circuit},
+};
+use ahash::HashMap;
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+pub type Env<'a> = Rc<HashMap<Name, Value<'a>>>;
+
+#[derive(Clone)]
+pub enum Frame<'a> {
+    Op1(Op1, Pos),
+    Op2L(Op2, &'a Term, Env<'a>, Pos),
+    Op2R(Op2, Value<'a>, Pos),
+    AppL(&'a Term, Env<'a>, Pos),
+    AppR(Value<'a>, Pos),
+    LetIn(&'a Name, &'a Term, Env<'a>),
+    Ifte(&'a Term, &'a Term, Env<'a>),
+    Raise(Pos),
+    Try(&'a Name, &'a Term, Env<'a>),
+    Perform(&'a Name, Pos),
+    Handle(&'a Term, Env<'a>),
+    CallCC(Pos),
+}
+
+// the frames between a perform and its handler, handler included
+pub struct Cont<'a> {
+    pub frames: RefCell<Option<Vec<Frame<'a>>>>,
+    pub pos: Pos,
+}
+
+impl Debug for Cont<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cont({})", self.pos)
+    }
+}
+
+// the whole stack at the point of a callcc, reinstated on every use
+pub struct Kont<'a>(pub Vec<Frame<'a>>);
+
+impl Debug for Kont<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Kont({})", self.0.len())
+    }
+}
+
+#[derive(Clone)]
+pub enum Control<'a> {
+    Eval(&'a Term, Env<'a>),
+    Return(Value<'a>),
+    Throw(Exn<Value<'a>>),
+}
+
+// cloning a machine snapshots it, refs are still shared with the original
+#[derive(Clone)]
+pub struct Machine<'a> {
+    pub ctrl: Control<'a>,
+    pub stack: Vec<Frame<'a>>,
+    pub steps: usize,
+    pub betas: usize,
+    pub ops: usize,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(env: Env<'a>, m: &'a Term) -> Self {
+        Machine {
+            ctrl: Control::Eval(m, env),
+            stack: vec![],
+            steps: 0,
+            betas: 0,
+            ops: 0,
+        }
+    }
+
+    pub fn result(&self) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        match (&self.ctrl, self.stack.is_empty()) {
+            (Control::Return(v), true) => Some(Ok(v.clone())),
+            (Control::Throw(exn), true) => Some(Err(exn.clone())),
+            (_, _) => None,
+        }
+    }
+
+    pub fn step(&mut self) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        if let Some(result) = self.result() {
+            return Some(result);
+        }
+        self.steps += 1;
+        let stack = &mut self.stack;
+        let ctrl = std::mem::replace(&mut self.ctrl, Control::Return(Value::Bool(false)));
+        self.ctrl = match ctrl {
+            Control::Eval(m, env) => step_eval(stack, m, env),
+            Control::Return(v) => {
+                let frame = stack.pop().unwrap();
+                match &frame {
+                    Frame::Op1(..) | Frame::Op2R(..) => self.ops += 1,
+                    Frame::Op2L(op2, ..) if short_circuit(op2, &v).is_some() => self.ops += 1,
+                    Frame::AppR(Value::Clo(_), _) => self.betas += 1,
+                    _ => (),
+                }
+                step_return(stack, frame, v)
+            }
+            Control::Throw(exn) => match stack.pop().unwrap() {
+                Frame::Try(x, n, env) => {
+                    let v = match exn {
+                        Exn::Raise(v, _) => v,
+                        Exn::Error(e) => Value::Int(e.code()),
+                    };
+                    Control::Eval(n, extend(&env, x, v))
+                }
+                _ => Control::Throw(exn),
+            },
+        };
+        None
+    }
+
+    pub fn run(&mut self) -> Result<Value<'a>, Exn<Value<'a>>> {
+        loop {
+            if let Some(result) = self.step() {
+                return result;
+            }
+        }
+    }
+}
+
+fn extend<'a>(env: &Env<'a>, x: &Name, v: Value<'a>) -> Env<'a> {
+    let mut env = (**env).clone();
+    env.insert(x.clone(), v);
+    Rc::new(env)
+}
+
+pub fn run<'a>(env: Env<'a>, m: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
+    Machine::new(env, m).run()
+}
+
+pub fn throw<'a>(k: &Kont<'a>, v: Value<'a>) -> Result<Value<'a>, Exn<Value<'a>>> {
+    let mut machine = Machine {
+        ctrl: Control::Return(v),
+        stack: k.0.clone(),
+        steps: 0,
+        betas: 0,
+        ops: 0,
+    };
+    machine.run()
+}
+
+pub fn resume<'a>(k: &Cont<'a>, v: Value<'a>) -> Result<Value<'a>, Exn<Value<'a>>> {
+    match k.frames.borrow_mut().take() {
+        Some(stack) => {
+            let mut machine = Machine {
+                ctrl: Control::Return(v),
+                stack,
+                steps: 0,
+                betas: 0,
+                ops: 0,
+            };
+            machine.run()
+        }
+        None => Err(Error::Resumed(k.pos).into()),
+    }
+}
+
+fn step_eval<'a>(stack: &mut Vec<Frame<'a>>, m0: &'a Term, env: Env<'a>) -> Control<'a> {
+    use Term::*;
+    match m0 {
+        Int(i) => Control::Return(Value::Int(*i)),
+        Float(f) => Control::Return(Value::Float(*f)),
+        Bool(b) => Control::Return(Value::Bool(*b)),
+        Var(x) => match env.get(x) {
+            Some(v) => Control::Return(v.clone()),
+            None => panic!("cannot find({:?})", x),
+        },
+        Op1(op1, m, pos) => {
+            stack.push(Frame::Op1(*op1, *pos));
+            Control::Eval(m, env)
+        }
+        Op2(op2, m, n, pos) => {
+            stack.push(Frame::Op2L(*op2, n, env.clone(), *pos));
+            Control::Eval(m, env)
+        }
+        Fun(f, x, m) => Control::Return(Value::Clo(Rc::new(Closure {
+            env: (*env).clone(),
+            func: f.clone(),
+            param: x.clone(),
+            body: m,
+        }))),
+        App(m, n, pos) => {
+            stack.push(Frame::AppL(n, env.clone(), *pos));
+            Control::Eval(m, env)
+        }
+        LetIn(x, m, n) => {
+            stack.push(Frame::LetIn(x, n, env.clone()));
+            Control::Eval(m, env)
+        }
+        Ifte(m, n1, n2) => {
+            stack.push(Frame::Ifte(n1, n2, env.clone()));
+            Control::Eval(m, env)
+        }
+        Raise(m, pos) => {
+            stack.push(Frame::Raise(*pos));
+            Control::Eval(m, env)
+        }
+        Try(m, x, n) => {
+            stack.push(Frame::Try(x, n, env.clone()));
+            Control::Eval(m, env)
+        }
+        Effect(_, m) => Control::Eval(m, env),
+        Perform(op, m, pos) => {
+            stack.push(Frame::Perform(op, *pos));
+            Control::Eval(m, env)
+        }
+        Handle(m, _, _, _) => {
+            stack.push(Frame::Handle(m0, env.clone()));
+            Control::Eval(m, env)
+        }
+        CallCC(m, pos) => {
+            stack.push(Frame::CallCC(*pos));
+            Control::Eval(m, env)
+        }
+    }
+}
+
+fn step_return<'a>(stack: &mut Vec<Frame<'a>>, frame: Frame<'a>, v: Value<'a>) -> Control<'a> {
+    match frame {
+        Frame::Op1(op1, pos) => match eval_op1(&op1, &v, pos) {
+            Ok(v) => Control::Return(v),
+            Err(e) => Control::Throw(e.into()),
+        },
+        Frame::Op2L(op2, n, env, pos) => match short_circuit(&op2, &v) {
+            Some(v) => Control::Return(v),
+            None => {
+                stack.push(Frame::Op2R(op2, v, pos));
+                Control::Eval(n, env)
+            }
+        },
+        Frame::Op2R(op2, m, pos) => match eval_op2(&op2, &m, &v, pos) {
+            Ok(v) => Control::Return(v),
+            Err(e) => Control::Throw(e.into()),
+        },
+        Frame::AppL(n, env, pos) => {
+            stack.push(Frame::AppR(v, pos));
+            Control::Eval(n, env)
+        }
+        Frame::AppR(f, _) => match &f {
+            Value::Clo(clo) => {
+                let mut env = clo.env.clone();
+                env.insert(clo.func.clone(), f.clone());
+                env.insert(clo.param.clone(), v);
+                Control::Eval(clo.body, Rc::new(env))
+            }
+            Value::Cont(k) => match k.frames.borrow_mut().take() {
+                Some(frames) => {
+                    stack.extend(frames);
+                    Control::Return(v)
+                }
+                None => Control::Throw(Error::Resumed(k.pos).into()),
+            },
+            Value::Kont(k) => {
+                *stack = k.0.clone();
+                Control::Return(v)
+            }
+            _ => panic!("eval_App({:?})", f),
+        },
+        Frame::LetIn(x, n, env) => Control::Eval(n, extend(&env, x, v)),
+        Frame::Ifte(n1, n2, env) => match v {
+            Value::Bool(true) => Control::Eval(n1, env),
+            Value::Bool(false) => Control::Eval(n2, env),
+            _ => panic!("eval_Ifte({:?})", v),
+        },
+        Frame::Raise(pos) => Control::Throw(Exn::Raise(v, pos)),
+        Frame::Try(..) => Control::Return(v),
+        Frame::Perform(op, pos) => perform(stack, op, v, pos),
+        Frame::Handle(Term::Handle(_, x, n, _), env) => Control::Eval(n, extend(&env, x, v)),
+        Frame::Handle(..) => unreachable!(),
+        Frame::CallCC(pos) => {
+            let k = Value::Kont(Rc::new(Kont(stack.clone())));
+            stack.push(Frame::AppR(v, pos));
+            Control::Return(k)
+        }
+    }
+}
+
+fn perform<'a>(stack: &mut Vec<Frame<'a>>, op: &Name, v: Value<'a>, pos: Pos) -> Control<'a> {
+    for i in (0..stack.len()).rev() {
+        if let Frame::Handle(Term::Handle(_, _, _, cases), env) = &stack[i]
+            && let Some((_, y, k, n)) = cases.iter().find(|case| &case.0 == op)
+        {
+            let mut env = (**env).clone();
+            let frames = stack.split_off(i);
+            let k0 = Cont {
+                frames: RefCell::new(Some(frames)),
+                pos,
+            };
+            env.insert(y.clone(), v);
+            env.insert(k.clone(), Value::Cont(Rc::new(k0)));
+            return Control::Eval(n, Rc::new(env));
+        }
+    }
+    Control::Throw(Error::Unhandled(op.to_string(), pos).into())
+}
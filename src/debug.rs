@@ -0,0 +1,395 @@
+use crate::{
+    ast1::*,
+    cek::{Control, Env, Frame, Machine},
+    error::{Error, Pos},
+    eval::Value,
+};
+use ahash::{HashMap, HashSet};
+use std::rc::Rc;
+
+pub enum Break {
+    Line(usize),
+    Func(String),
+}
+
+pub struct Call<'a> {
+    pub id: usize,
+    pub name: String,
+    pub arg: Value<'a>,
+    pub pos: Pos,
+    // length of the machine stack while the body is evaluated, increasing
+    // from the bottom of the call stack
+    depth: usize,
+}
+
+// the function calls in progress on a cek machine, a tail call takes over
+// the entry of its caller so that a loop does not grow the stack
+pub struct CallStack<'a> {
+    // function names by the address of their bodies
+    names: HashMap<*const Term, String>,
+    pub calls: Vec<Call<'a>>,
+    next: usize,
+}
+
+// drives a cek machine, eval itself cannot be paused
+pub struct Debugger<'a> {
+    machine: Machine<'a>,
+    pub stack: CallStack<'a>,
+    pub breaks: Vec<Break>,
+    env: Env<'a>,
+    term: &'a Term,
+    pos: Pos,
+    // line of the last term with a position, line breakpoints trigger on entry
+    line: usize,
+    // lines with a term that has a position
+    lines: HashSet<usize>,
+    finished: bool,
+}
+
+fn pos_of(m: &Term) -> Option<Pos> {
+    use Term::*;
+    match m {
+        Op2(_, _, _, pos)
+        | App(_, _, pos)
+        | Raise(_, pos)
+        | Perform(_, _, pos)
+        | CallCC(_, pos) => Some(*pos),
+        _ => None,
+    }
+}
+
+fn collect_lines(m0: &Term, lines: &mut HashSet<usize>) {
+    use Term::*;
+    if let Some(pos) = pos_of(m0) {
+        lines.insert(pos.line);
+    }
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => (),
+        Op1(_, m, _)
+        | Fun(_, _, m)
+        | Raise(m, _)
+        | Effect(_, m)
+        | Perform(_, m, _)
+        | CallCC(m, _) => collect_lines(m, lines),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            collect_lines(m, lines);
+            collect_lines(n, lines)
+        }
+        Ifte(m, n1, n2) => {
+            collect_lines(m, lines);
+            collect_lines(n1, lines);
+            collect_lines(n2, lines)
+        }
+        Handle(m, _, n, cases) => {
+            collect_lines(m, lines);
+            collect_lines(n, lines);
+            for (_, _, _, n) in cases {
+                collect_lines(n, lines)
+            }
+        }
+    }
+}
+
+// curried parameters of a named function share its name
+fn collect_names(m0: &Term, name: Option<String>, names: &mut HashMap<*const Term, String>) {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => (),
+        Fun(f, x, m) => {
+            let name = match name {
+                _ if !f.to_string().is_empty() => f.to_string(),
+                Some(name) => name,
+                None => format!("fun {}", x),
+            };
+            names.insert(m.as_ref() as *const Term, name.clone());
+            collect_names(m, Some(name), names)
+        }
+        LetIn(x, m, n) => {
+            collect_names(m, Some(x.to_string()), names);
+            collect_names(n, None, names)
+        }
+        Op1(_, m, _) | Raise(m, _) | Effect(_, m) | Perform(_, m, _) | CallCC(m, _) => {
+            collect_names(m, None, names)
+        }
+        Op2(_, m, n, _) | App(m, n, _) | Try(m, _, n) => {
+            collect_names(m, None, names);
+            collect_names(n, None, names)
+        }
+        Ifte(m, n1, n2) => {
+            collect_names(m, None, names);
+            collect_names(n1, None, names);
+            collect_names(n2, None, names)
+        }
+        Handle(m, _, n, cases) => {
+            collect_names(m, None, names);
+            collect_names(n, None, names);
+            for (_, _, _, n) in cases {
+                collect_names(n, None, names)
+            }
+        }
+    }
+}
+
+const HELP: &str = "\
+break LINE | break NAME   stop on a source line or on calls to a function,
+                          a line is stopped on when it is entered from another
+                          line at a binary operator, call, raise, perform or
+                          callcc, a line with none of these cannot be stopped on
+delete                    remove all breakpoints
+step                      evaluate up to the next subterm
+next                      step over the current term
+finish                    run until the current function returns
+continue                  run until a breakpoint or the end
+env                       show the current environment
+print NAME                show the value of a variable
+stack                     show the call stack
+where                     show the current term";
+
+impl<'a> CallStack<'a> {
+    pub fn new(m: &'a Term) -> Self {
+        let mut names = HashMap::default();
+        collect_names(m, None, &mut names);
+        CallStack {
+            names,
+            calls: vec![],
+            next: 0,
+        }
+    }
+
+    pub fn show(&self, v: &Value) -> String {
+        match v {
+            Value::Int(i) => i.to_string(),
+            Value::Float(f) => format!("{:?}", f),
+            Value::Bool(b) => b.to_string(),
+            Value::Clo(clo) => match self.names.get(&(clo.body as *const Term)) {
+                Some(name) => format!("<{}>", name),
+                None => format!("<fun {}>", clo.param),
+            },
+            Value::Ref(r) => format!("ref {}", self.show(&r.borrow())),
+            Value::Cont(_) | Value::Kont(_) => String::from("<continuation>"),
+            Value::Thunk(_) => String::from("<thunk>"),
+        }
+    }
+
+    // one machine transition, returns true when it enters a function
+    pub fn step(&mut self, machine: &mut Machine<'a>) -> bool {
+        let call = match (&machine.ctrl, machine.stack.last()) {
+            (Control::Return(v), Some(Frame::AppR(Value::Clo(clo), pos))) => {
+                let name = self.names.get(&(clo.body as *const Term)).cloned();
+                Some((name, v.clone(), *pos))
+            }
+            _ => None,
+        };
+        if machine.step().is_some() {
+            self.calls.clear();
+            return false;
+        }
+        let depth = machine.stack.len();
+        let returned = matches!(machine.ctrl, Control::Return(_));
+        while let Some(call) = self.calls.last()
+            && (call.depth > depth || (returned && call.depth == depth))
+        {
+            self.calls.pop();
+        }
+        match (call, &machine.ctrl) {
+            (Some((name, arg, pos)), Control::Eval(..)) => {
+                if self.calls.last().is_some_and(|call| call.depth == depth) {
+                    self.calls.pop();
+                }
+                self.next += 1;
+                self.calls.push(Call {
+                    id: self.next,
+                    name: name.unwrap_or_else(|| String::from("fun")),
+                    arg,
+                    pos,
+                    depth,
+                });
+                true
+            }
+            (_, _) => false,
+        }
+    }
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(m: &'a Term) -> Self {
+        Debugger {
+            machine: Machine::new(Rc::new(HashMap::default()), m),
+            stack: CallStack::new(m),
+            breaks: vec![],
+            env: Rc::new(HashMap::default()),
+            term: m,
+            pos: pos_of(m).unwrap_or(Pos { line: 1, col: 1 }),
+            line: 0,
+            lines: {
+                let mut lines = HashSet::default();
+                collect_lines(m, &mut lines);
+                lines
+            },
+            finished: false,
+        }
+    }
+
+    fn show(&self, v: &Value) -> String {
+        self.stack.show(v)
+    }
+
+    fn location(&self) -> String {
+        let mut term = self.term.to_string();
+        if term.chars().count() > 60 {
+            term = term.chars().take(57).collect::<String>() + "...";
+        }
+        format!("{}  {}", self.pos, term)
+    }
+
+    // one machine transition, describes the breakpoint it hits if any
+    fn step(&mut self) -> Option<String> {
+        let entered = self.stack.step(&mut self.machine);
+        if self.machine.result().is_some() {
+            self.finished = true;
+            return None;
+        }
+        let mut hit = None;
+        if let Control::Eval(m, env) = &self.machine.ctrl {
+            self.term = m;
+            self.env = env.clone();
+            if let Some(pos) = pos_of(m) {
+                if pos.line != self.line
+                    && let Some(i) = self
+                        .breaks
+                        .iter()
+                        .position(|b| matches!(b, Break::Line(l) if *l == pos.line))
+                {
+                    hit = Some(format!("breakpoint {}, line {}", i + 1, pos.line))
+                }
+                self.pos = pos;
+                self.line = pos.line;
+            }
+            if entered {
+                let call = self.stack.calls.last().unwrap();
+                if let Some(i) = self
+                    .breaks
+                    .iter()
+                    .position(|b| matches!(b, Break::Func(f) if *f == call.name))
+                {
+                    let arg = self.show(&call.arg);
+                    hit = Some(format!(
+                        "breakpoint {}, {} {} called at {}",
+                        i + 1,
+                        call.name,
+                        arg,
+                        call.pos
+                    ))
+                }
+            }
+        }
+        hit
+    }
+
+    fn is_eval(&self) -> bool {
+        matches!(self.machine.ctrl, Control::Eval(..))
+    }
+
+    fn run_until(&mut self, stop: impl Fn(&Self) -> bool) -> String {
+        if self.finished {
+            return String::from("the program has finished");
+        }
+        loop {
+            let hit = self.step();
+            if self.finished {
+                return match self.machine.result().unwrap() {
+                    Ok(v) => format!("value : {}", self.show(&v)),
+                    Err(e) => format!("runtime error : {}", Error::from(e)),
+                };
+            }
+            if let Some(hit) = hit {
+                return format!("{}\n{}", hit, self.location());
+            }
+            if stop(self) {
+                return self.location();
+            }
+        }
+    }
+
+    pub fn command(&mut self, line: &str) -> String {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["break" | "b", arg] => {
+                match arg.parse() {
+                    Ok(line) if !self.lines.contains(&line) => {
+                        return format!("line {} has no operator or call to stop on", line);
+                    }
+                    Ok(line) => self.breaks.push(Break::Line(line)),
+                    Err(_) => self.breaks.push(Break::Func(arg.to_string())),
+                }
+                format!("breakpoint {} on {}", self.breaks.len(), arg)
+            }
+            ["delete" | "d"] => {
+                self.breaks.clear();
+                String::from("all breakpoints removed")
+            }
+            ["step" | "s"] => self.run_until(|d| d.is_eval()),
+            ["next" | "n"] => {
+                let depth = self.machine.stack.len();
+                self.run_until(move |d| d.is_eval() && d.machine.stack.len() <= depth)
+            }
+            ["finish" | "f"] => {
+                let calls = self.stack.calls.len();
+                let name = self.stack.calls.last().map(|call| call.name.clone());
+                let out = self.run_until(move |d| d.stack.calls.len() < calls);
+                match (&self.machine.ctrl, name) {
+                    (Control::Return(v), Some(name)) if self.stack.calls.len() < calls => {
+                        format!("{} returned {}", name, self.show(v))
+                    }
+                    (_, _) => out,
+                }
+            }
+            ["continue" | "c"] => self.run_until(|_| false),
+            ["env" | "e"] => {
+                let mut xs: Vec<_> = self.env.iter().collect();
+                xs.sort_by_key(|(x, _)| *x);
+                let xs: Vec<String> = xs
+                    .into_iter()
+                    .map(|(x, v)| format!("{} = {}", x, self.show(v)))
+                    .collect();
+                xs.join("\n")
+            }
+            ["print" | "p", name] => {
+                match self
+                    .env
+                    .iter()
+                    .filter(|(x, _)| x.to_string() == *name)
+                    .max_by(|(x, _), (y, _)| x.cmp(y))
+                {
+                    Some((_, v)) => self.show(v),
+                    None => format!("{} is not in scope", name),
+                }
+            }
+            ["stack" | "bt"] => {
+                let calls: Vec<String> = self
+                    .stack
+                    .calls
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, call)| {
+                        format!(
+                            "#{} {} {} called at {}",
+                            i,
+                            call.name,
+                            self.show(&call.arg),
+                            call.pos
+                        )
+                    })
+                    .collect();
+                if calls.is_empty() {
+                    return String::from("no function calls");
+                }
+                calls.join("\n")
+            }
+            ["where" | "w"] => self.location(),
+            ["help" | "h"] => String::from(HELP),
+            _ => format!("unknown command {:?}, try help", line.trim()),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::{self, Debug, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    DivByZero(Pos),
+    Overflow(Pos),
+    Incomparable(Pos),
+    FunctionalValue(Pos),
+    Uncaught(String, Pos),
+    Unhandled(String, Pos),
+    Resumed(Pos),
+    Unsupported(String),
+}
+
+impl Error {
+    // the value a `try ... with x => ...` handler receives in x
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::DivByZero(_) => 1,
+            Error::Overflow(_) => 2,
+            Error::Incomparable(_) => 3,
+            Error::FunctionalValue(_) => 4,
+            Error::Uncaught(_, _) => 5,
+            Error::Unhandled(_, _) => 6,
+            Error::Resumed(_) => 7,
+            Error::Unsupported(_) => 8,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DivByZero(pos) => write!(f, "{}: division by zero", pos),
+            Error::Overflow(pos) => write!(f, "{}: integer overflow", pos),
+            Error::Incomparable(pos) => write!(f, "{}: comparing values of different types", pos),
+            Error::FunctionalValue(pos) => write!(f, "{}: comparing functional values", pos),
+            Error::Uncaught(v, pos) => write!(f, "{}: uncaught exception {}", pos, v),
+            Error::Unhandled(op, pos) => write!(f, "{}: unhandled effect {}", pos, op),
+            Error::Resumed(pos) => write!(f, "{}: continuation resumed more than once", pos),
+            Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Exn<V> {
+    Error(Error),
+    Raise(V, Pos),
+}
+
+impl<V> From<Error> for Exn<V> {
+    fn from(e: Error) -> Self {
+        Exn::Error(e)
+    }
+}
+
+impl<V: Debug> From<Exn<V>> for Error {
+    fn from(exn: Exn<V>) -> Self {
+        match exn {
+            Exn::Error(e) => e,
+            Exn::Raise(v, pos) => Error::Uncaught(format!("{:?}", v), pos),
+        }
+    }
+}
@@ -1,11 +1,19 @@
-use crate::{ast1::*, names::Name};
+use crate::{
+    ast1::*,
+    cek::{self, Cont, Kont},
+    error::{Error, Exn},
+    lazy::Thunk,
+    names::Name,
+    ops::{Prim, View, eval_op1, eval_op2, short_circuit},
+};
 use ahash::HashMap;
 use std::{cell::RefCell, fmt::Debug, rc::Rc};
 
 type Env<'a> = RefCell<HashMap<Name, Value<'a>>>;
 
+// closures never change once built, every call gets its own environment
 pub struct Closure<'a> {
-    pub env: Env<'a>,
+    pub env: HashMap<Name, Value<'a>>,
     pub func: Name,
     pub param: Name,
     pub body: &'a Term,
@@ -20,105 +28,189 @@ impl Debug for Closure<'_> {
 #[derive(Debug, Clone)]
 pub enum Value<'a> {
     Int(i32),
+    Float(f64),
     Bool(bool),
     Clo(Rc<Closure<'a>>),
+    Ref(Rc<RefCell<Value<'a>>>),
+    Cont(Rc<Cont<'a>>),
+    Kont(Rc<Kont<'a>>),
+    Thunk(Rc<RefCell<Thunk<'a>>>),
 }
 
-pub fn eval<'a>(env: &Env<'a>, m0: &'a Term) -> Value<'a> {
+// the bindings made during one call, innermost last, on top of those its
+// closure captured, closures are never modified so calls cannot disturb
+// each other
+struct Scope<'a, 'b> {
+    captured: &'b HashMap<Name, Value<'a>>,
+    local: RefCell<Vec<(Name, Value<'a>)>>,
+}
+
+impl<'a> Scope<'a, '_> {
+    fn get(&self, x: &Name) -> Option<Value<'a>> {
+        match self.local.borrow().iter().rev().find(|(y, _)| y == x) {
+            Some((_, v)) => Some(v.clone()),
+            None => self.captured.get(x).cloned(),
+        }
+    }
+
+    // x is bound in m only
+    fn bind<T>(&self, x: &Name, v: Value<'a>, m: impl FnOnce() -> T) -> T {
+        self.local.borrow_mut().push((x.clone(), v));
+        let result = m();
+        self.local.borrow_mut().pop();
+        result
+    }
+
+    fn snapshot(&self) -> HashMap<Name, Value<'a>> {
+        let mut env = self.captured.clone();
+        for (x, v) in self.local.borrow().iter() {
+            env.insert(x.clone(), v.clone());
+        }
+        env
+    }
+}
+
+pub fn eval<'a>(env: &Env<'a>, m0: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
+    // a continuation may be called after its callcc returned, which needs
+    // the rest of the whole program, so such a program runs on the cek machine
+    if uses_callcc(m0) {
+        return cek::run(Rc::new(env.borrow().clone()), m0);
+    }
+    let scope = Scope {
+        captured: &env.borrow(),
+        local: RefCell::default(),
+    };
+    eval_in(&scope, m0)
+}
+
+fn eval_in<'a>(scope: &Scope<'a, '_>, m0: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
     use Term::*;
     match m0 {
-        Int(i) => Value::Int(*i),
-        Bool(b) => Value::Bool(*b),
-        Var(x) => match env.borrow().get(x) {
-            Some(v) => v.clone(),
+        Int(i) => Ok(Value::Int(*i)),
+        Float(f) => Ok(Value::Float(*f)),
+        Bool(b) => Ok(Value::Bool(*b)),
+        Var(x) => match scope.get(x) {
+            Some(v) => Ok(v),
             None => {
                 println!("cannot find({:?})", x);
                 panic!()
             }
         },
-        Op1(op1, m) => {
-            let m = eval(env, m);
-            eval_op1(op1, &m)
+        Op1(op1, m, pos) => {
+            let m = eval_in(scope, m)?;
+            Ok(eval_op1(op1, &m, *pos)?)
         }
-        Op2(op2, m, n) => {
-            let m = eval(env, m);
-            let n = eval(env, n);
-            eval_op2(op2, &m, &n)
+        Op2(op2, m, n, pos) => {
+            let m = eval_in(scope, m)?;
+            if let Some(v) = short_circuit(op2, &m) {
+                return Ok(v);
+            }
+            let n = eval_in(scope, n)?;
+            Ok(eval_op2(op2, &m, &n, *pos)?)
         }
-        Fun(f, x, m) => Value::Clo(Rc::new(Closure {
-            env: env.clone(),
+        Fun(f, x, m) => Ok(Value::Clo(Rc::new(Closure {
+            env: scope.snapshot(),
             func: f.clone(),
             param: x.clone(),
             body: m,
-        })),
-        App(m, n) => {
-            let m0 = eval(env, m);
-            let n0 = eval(env, n);
+        }))),
+        App(m, n, _) => {
+            let m0 = eval_in(scope, m)?;
+            let n0 = eval_in(scope, n)?;
             match &m0 {
                 Value::Clo(clo) => {
-                    let local = &clo.env;
-                    let opt1 = local.borrow_mut().insert(clo.func.clone(), m0.clone());
-                    let opt2 = local.borrow_mut().insert(clo.param.clone(), n0);
-                    let result = eval(local, clo.body);
-                    if let Some(v) = opt1 {
-                        local.borrow_mut().insert(clo.func.clone(), v);
-                    } else {
-                        local.borrow_mut().remove(&clo.func);
+                    let local = vec![(clo.func.clone(), m0.clone()), (clo.param.clone(), n0)];
+                    let scope = Scope {
+                        captured: &clo.env,
+                        local: RefCell::new(local),
                     };
-                    if let Some(v) = opt2 {
-                        local.borrow_mut().insert(clo.param.clone(), v);
-                    } else {
-                        local.borrow_mut().remove(&clo.param);
-                    };
-                    return result;
+                    eval_in(&scope, clo.body)
                 }
+                Value::Cont(k) => cek::resume(k, n0),
+                Value::Kont(k) => cek::throw(k, n0),
                 _ => panic!("eval_App({:?})", m0),
             }
         }
         LetIn(x, m, n) => {
-            let m = eval(env, m);
-            let opt = env.borrow_mut().insert(x.clone(), m);
-            let result = eval(env, n);
-            opt.and_then(|v| env.borrow_mut().insert(x.clone(), v));
-            return result;
+            let m = eval_in(scope, m)?;
+            scope.bind(x, m, || eval_in(scope, n))
         }
         Ifte(m, n1, n2) => {
-            let m = eval(env, m);
+            let m = eval_in(scope, m)?;
             match m {
-                Value::Bool(true) => eval(env, n1),
-                Value::Bool(false) => eval(env, n2),
+                Value::Bool(true) => eval_in(scope, n1),
+                Value::Bool(false) => eval_in(scope, n2),
                 _ => panic!("eval_Ifte({:?})", m0),
             }
         }
+        Raise(m, pos) => {
+            let m = eval_in(scope, m)?;
+            Err(Exn::Raise(m, *pos))
+        }
+        Try(m, x, n) => {
+            let v = match eval_in(scope, m) {
+                Ok(v) => return Ok(v),
+                Err(Exn::Raise(v, _)) => v,
+                Err(Exn::Error(e)) => Value::Int(e.code()),
+            };
+            scope.bind(x, v, || eval_in(scope, n))
+        }
+        Effect(_, m) => eval_in(scope, m),
+        Perform(op, m, pos) => {
+            eval_in(scope, m)?;
+            Err(Error::Unhandled(op.to_string(), *pos).into())
+        }
+        Handle(..) => cek::run(Rc::new(scope.snapshot()), m0),
+        // only reached through a closure eval was given in env
+        CallCC(..) => {
+            Err(Error::Unsupported(String::from("callcc in a closure passed to eval")).into())
+        }
     }
 }
 
-fn eval_op1<'a>(op: &'a Op1, m: &Value<'a>) -> Value<'a> {
-    use self::Op1::*;
-    use Value::*;
-    match (op, m) {
-        (Not, Bool(b)) => Value::Bool(!b),
-        (Neg, Int(i)) => Value::Int(-i),
-        (_, _) => panic!("eval_op1({:?}, {:?})", op, m),
+fn uses_callcc(m0: &Term) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => false,
+        CallCC(..) => true,
+        Op1(_, m, _) | Fun(_, _, m) | Raise(m, _) | Effect(_, m) | Perform(_, m, _) => {
+            uses_callcc(m)
+        }
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            uses_callcc(m) || uses_callcc(n)
+        }
+        Ifte(m, n1, n2) => uses_callcc(m) || uses_callcc(n1) || uses_callcc(n2),
+        Handle(m, _, n, cases) => {
+            uses_callcc(m) || uses_callcc(n) || cases.iter().any(|(.., m)| uses_callcc(m))
+        }
     }
 }
 
-fn eval_op2<'a>(op: &'a Op2, m: &Value<'a>, n: &Value<'a>) -> Value<'a> {
-    use self::Op2::*;
-    use Value::*;
-    match (op, m, n) {
-        (Add, Int(i), Int(j)) => Value::Int(i + j),
-        (Sub, Int(i), Int(j)) => Value::Int(i - j),
-        (Mul, Int(i), Int(j)) => Value::Int(i * j),
-        (Div, Int(i), Int(j)) => Value::Int(i / j),
-        (Lte, Int(i), Int(j)) => Value::Bool(i <= j),
-        (Gte, Int(i), Int(j)) => Value::Bool(i >= j),
-        (Lt, Int(i), Int(j)) => Value::Bool(i < j),
-        (Gt, Int(i), Int(j)) => Value::Bool(i > j),
-        (Eq, Int(i), Int(j)) => Value::Bool(i == j),
-        (Neq, Int(i), Int(j)) => Value::Bool(i != j),
-        (And, Bool(i), Bool(j)) => Value::Bool(*i && *j),
-        (Or, Bool(i), Bool(j)) => Value::Bool(*i || *j),
-        (_, _, _) => panic!("eval_op2({:?}, {:?}, {:?})", op, m, n),
+impl Prim for Value<'_> {
+    fn int(i: i32) -> Self {
+        Value::Int(i)
+    }
+
+    fn float(f: f64) -> Self {
+        Value::Float(f)
+    }
+
+    fn bool(b: bool) -> Self {
+        Value::Bool(b)
+    }
+
+    fn reference(r: Rc<RefCell<Self>>) -> Self {
+        Value::Ref(r)
+    }
+
+    fn view(&self) -> View<'_, Self> {
+        match self {
+            Value::Int(i) => View::Int(*i),
+            Value::Float(f) => View::Float(*f),
+            Value::Bool(b) => View::Bool(*b),
+            Value::Ref(r) => View::Ref(r),
+            Value::Clo(_) | Value::Cont(_) | Value::Kont(_) => View::Fun,
+            Value::Thunk(_) => View::Other,
+        }
     }
 }
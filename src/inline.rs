@@ -0,0 +1,169 @@
+use crate::{ast1::*, error::Pos, names::Name};
+use ahash::HashMap;
+
+// functions up to this size are inlined at every call
+const MAX_SIZE: usize = 40;
+// the number of calls inlined in one program, self application would
+// otherwise unfold forever
+const FUEL: usize = 1_000;
+
+// beta reduces calls of known functions that do not call themselves, the
+// argument is let bound so that it is still evaluated once and before the
+// body, and the body is copied with fresh binders
+pub fn inline(m: &Term) -> Box<Term> {
+    Inliner {
+        known: HashMap::default(),
+        fuel: FUEL,
+    }
+    .inline(m)
+}
+
+struct Inliner {
+    known: HashMap<Name, Box<Term>>,
+    fuel: usize,
+}
+
+pub fn size(m0: &Term) -> usize {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => 1,
+        Op1(_, m, _)
+        | Fun(_, _, m)
+        | Raise(m, _)
+        | Effect(_, m)
+        | Perform(_, m, _)
+        | CallCC(m, _) => 1 + size(m),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => 1 + size(m) + size(n),
+        Ifte(m, n1, n2) => 1 + size(m) + size(n1) + size(n2),
+        Handle(m, _, n, cases) => {
+            1 + size(m) + size(n) + cases.iter().map(|(.., m)| size(m)).sum::<usize>()
+        }
+    }
+}
+
+fn inlinable(m0: &Term) -> bool {
+    match m0 {
+        Term::Fun(f, _, m) => !free_vars(m).contains(f) && size(m0) <= MAX_SIZE,
+        _ => false,
+    }
+}
+
+impl Inliner {
+    fn inline(&mut self, m0: &Term) -> Box<Term> {
+        use Term::*;
+        match m0 {
+            Int(_) | Float(_) | Bool(_) | Var(_) => Box::new(m0.clone()),
+            Op1(op, m, pos) => op1(*op, self.inline(m), *pos),
+            Op2(op, m, n, pos) => op2(*op, self.inline(m), self.inline(n), *pos),
+            Fun(f, x, m) => fun(f.clone(), x.clone(), self.inline(m)),
+            App(m, n, pos) => {
+                let m = self.inline(m);
+                let n = self.inline(n);
+                self.beta(*m, n, *pos)
+            }
+            LetIn(x, m, n) => {
+                let m = self.inline(m);
+                self.bind(x.clone(), m, n)
+            }
+            Ifte(m, n1, n2) => ifte(self.inline(m), self.inline(n1), self.inline(n2)),
+            Raise(m, pos) => raise(self.inline(m), *pos),
+            Try(m, x, n) => trywith(self.inline(m), x.clone(), self.inline(n)),
+            Effect(op, m) => effect(op.clone(), self.inline(m)),
+            Perform(op, m, pos) => perform(op.clone(), self.inline(m), *pos),
+            Handle(m, x, n, cases) => {
+                let m = self.inline(m);
+                let n = self.inline(n);
+                let cases = cases
+                    .iter()
+                    .map(|(op, y, k, m)| (op.clone(), y.clone(), k.clone(), self.inline(m)))
+                    .collect();
+                handle(m, x.clone(), n, cases)
+            }
+            CallCC(m, pos) => callcc(self.inline(m), *pos),
+        }
+    }
+
+    // let x := m in n with m already inlined, the bindings of m are floated
+    // out so that a function computed by a let is known as well, and a
+    // variable or a literal is substituted
+    fn bind(&mut self, x: Name, m: Box<Term>, n: &Term) -> Box<Term> {
+        use Term::*;
+        match *m {
+            LetIn(y, m, m1) => {
+                let m1 = self.bind(x, m1, n);
+                letin(y, m, m1)
+            }
+            Int(_) | Float(_) | Bool(_) | Var(_) => self.inline(&substitute(n, &x, &m)),
+            m => {
+                if inlinable(&m) {
+                    self.known.insert(x.clone(), Box::new(m.clone()));
+                }
+                letin(x, Box::new(m), self.inline(n))
+            }
+        }
+    }
+
+    fn beta(&mut self, m: Term, n: Box<Term>, pos: Pos) -> Box<Term> {
+        use Term::*;
+        match m {
+            Var(f) if self.fuel > 0 && self.known.contains_key(&f) => {
+                let m = rename(&self.known[&f], &mut HashMap::default());
+                self.beta(*m, n, pos)
+            }
+            Fun(f, x, m) if self.fuel > 0 && !free_vars(&m).contains(&f) => {
+                self.fuel -= 1;
+                self.bind(x, n, &m)
+            }
+            LetIn(y, m, body) => letin(y, m, self.beta(*body, n, pos)),
+            m => app(Box::new(m), n, pos),
+        }
+    }
+}
+
+// a copy of m0 where every binder is a fresh name
+fn rename(m0: &Term, env: &mut HashMap<Name, Name>) -> Box<Term> {
+    use Term::*;
+    let fresh = |env: &mut HashMap<Name, Name>, x: &Name| {
+        let y = x.fresh();
+        env.insert(x.clone(), y.clone());
+        y
+    };
+    let get = |env: &HashMap<Name, Name>, x: &Name| env.get(x).unwrap_or(x).clone();
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => Box::new(m0.clone()),
+        Var(x) => var(get(env, x)),
+        Op1(op, m, pos) => op1(*op, rename(m, env), *pos),
+        Op2(op, m, n, pos) => op2(*op, rename(m, env), rename(n, env), *pos),
+        Fun(f, x, m) => fun(fresh(env, f), fresh(env, x), rename(m, env)),
+        App(m, n, pos) => app(rename(m, env), rename(n, env), *pos),
+        LetIn(x, m, n) => {
+            let m = rename(m, env);
+            let x = match m.as_ref() {
+                // let rec, the function was renamed along with its name
+                Fun(f, ..) if env.get(x) == Some(f) => f.clone(),
+                _ => fresh(env, x),
+            };
+            letin(x, m, rename(n, env))
+        }
+        Ifte(m, n1, n2) => ifte(rename(m, env), rename(n1, env), rename(n2, env)),
+        Raise(m, pos) => raise(rename(m, env), *pos),
+        Try(m, x, n) => {
+            let m = rename(m, env);
+            trywith(m, fresh(env, x), rename(n, env))
+        }
+        Effect(op, m) => effect(fresh(env, op), rename(m, env)),
+        Perform(op, m, pos) => perform(get(env, op), rename(m, env), *pos),
+        Handle(m, x, n, cases) => {
+            let m = rename(m, env);
+            let cases = cases
+                .iter()
+                .map(|(op, y, k, m)| {
+                    let (y, k) = (fresh(env, y), fresh(env, k));
+                    (get(env, op), y, k, rename(m, env))
+                })
+                .collect();
+            handle(m, fresh(env, x), rename(n, env), cases)
+        }
+        CallCC(m, pos) => callcc(rename(m, env), *pos),
+    }
+}
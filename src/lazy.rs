@@ -0,0 +1,272 @@
+use crate::{
+    ast1::*,
+    error::{Error, Exn, Pos},
+    eval::{Closure, Value},
+    names::Name,
+    ops::{eval_op1, eval_op2, short_circuit},
+};
+use ahash::HashMap;
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+pub type Env<'a> = Rc<HashMap<Name, Value<'a>>>;
+
+pub enum Thunk<'a> {
+    Delayed(Env<'a>, &'a Term),
+    Forced(Value<'a>),
+}
+
+impl Debug for Thunk<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Thunk::Delayed(_, _) => write!(f, "Delayed"),
+            Thunk::Forced(v) => write!(f, "Forced({:?})", v),
+        }
+    }
+}
+
+// forcing a thunk pushes an Update frame instead of recursing, chains of
+// millions of thunks such as test1's `x + 1` only grow the heap stack
+pub enum Frame<'a> {
+    Op1(Op1, Pos),
+    Op2L(Op2, &'a Term, Env<'a>, Pos),
+    Op2R(Op2, Value<'a>, Pos),
+    Arg(Value<'a>),
+    Ifte(&'a Term, &'a Term, Env<'a>),
+    Raise(Pos),
+    Try(&'a Name, &'a Term, Env<'a>),
+    Update(Rc<RefCell<Thunk<'a>>>),
+}
+
+enum Control<'a> {
+    Eval(&'a Term, Env<'a>),
+    Return(Value<'a>),
+    Throw(Exn<Value<'a>>),
+}
+
+fn extend<'a>(env: &Env<'a>, x: &Name, v: Value<'a>) -> Env<'a> {
+    let mut env = (**env).clone();
+    env.insert(x.clone(), v);
+    Rc::new(env)
+}
+
+fn delay<'a>(env: &Env<'a>, m: &'a Term) -> Value<'a> {
+    match m {
+        Term::Int(i) => Value::Int(*i),
+        Term::Float(f) => Value::Float(*f),
+        Term::Bool(b) => Value::Bool(*b),
+        Term::Var(x) => env.get(x).unwrap().clone(),
+        _ => Value::Thunk(Rc::new(RefCell::new(Thunk::Delayed(env.clone(), m)))),
+    }
+}
+
+// free variables of every Fun evaluated so far, keyed by its address
+type FreeVars = HashMap<*const Term, Rc<[Name]>>;
+
+pub struct Machine<'a> {
+    ctrl: Control<'a>,
+    stack: Vec<Frame<'a>>,
+    fvs: FreeVars,
+    // call-by-name when false, thunks are then re-evaluated on every use
+    memo: bool,
+    pub steps: usize,
+    pub betas: usize,
+    pub ops: usize,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(env: Env<'a>, m: &'a Term, memo: bool) -> Self {
+        Machine {
+            ctrl: Control::Eval(m, env),
+            stack: vec![],
+            fvs: HashMap::default(),
+            memo,
+            steps: 0,
+            betas: 0,
+            ops: 0,
+        }
+    }
+
+    pub fn step(&mut self) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        self.steps += 1;
+        let ctrl = std::mem::replace(&mut self.ctrl, Control::Return(Value::Bool(false)));
+        self.ctrl = match ctrl {
+            Control::Eval(m, env) => self.step_eval(m, env),
+            Control::Return(v) => match self.stack.pop() {
+                Some(frame) => self.step_return(frame, v),
+                None => {
+                    self.ctrl = Control::Return(v.clone());
+                    return Some(Ok(v));
+                }
+            },
+            Control::Throw(exn) => match self.stack.pop() {
+                // no try can recover from running on the wrong machine
+                Some(Frame::Try(x, n, env))
+                    if !matches!(exn, Exn::Error(Error::Unsupported(_))) =>
+                {
+                    let v = match exn {
+                        Exn::Raise(v, _) => v,
+                        Exn::Error(e) => Value::Int(e.code()),
+                    };
+                    Control::Eval(n, extend(&env, x, v))
+                }
+                Some(_) => Control::Throw(exn),
+                None => {
+                    self.ctrl = Control::Throw(exn.clone());
+                    return Some(Err(exn));
+                }
+            },
+        };
+        None
+    }
+
+    // evaluates to weak head normal form, the result is never a thunk
+    pub fn run(&mut self) -> Result<Value<'a>, Exn<Value<'a>>> {
+        loop {
+            if let Some(result) = self.step() {
+                return result;
+            }
+        }
+    }
+
+    fn force(&mut self, v: Value<'a>) -> Control<'a> {
+        match v {
+            Value::Thunk(t) => {
+                let ctrl = match &*t.borrow() {
+                    Thunk::Forced(v) => return Control::Return(v.clone()),
+                    Thunk::Delayed(env, m) => Control::Eval(m, env.clone()),
+                };
+                if self.memo {
+                    self.stack.push(Frame::Update(t));
+                }
+                ctrl
+            }
+            v => Control::Return(v),
+        }
+    }
+
+    fn step_eval(&mut self, m0: &'a Term, env: Env<'a>) -> Control<'a> {
+        use Term::*;
+        let stack = &mut self.stack;
+        match m0 {
+            Int(i) => Control::Return(Value::Int(*i)),
+            Float(f) => Control::Return(Value::Float(*f)),
+            Bool(b) => Control::Return(Value::Bool(*b)),
+            Var(x) => match env.get(x) {
+                Some(v) => self.force(v.clone()),
+                None => panic!("cannot find({:?})", x),
+            },
+            Op1(op1, m, pos) => {
+                stack.push(Frame::Op1(*op1, *pos));
+                Control::Eval(m, env)
+            }
+            Op2(op2, m, n, pos) => {
+                stack.push(Frame::Op2L(*op2, n, env.clone(), *pos));
+                Control::Eval(m, env)
+            }
+            // closures only keep their free variables, otherwise every thunk
+            // would hold on to a copy of the whole environment
+            Fun(f, x, m) => {
+                let ys = self
+                    .fvs
+                    .entry(m0 as *const Term)
+                    .or_insert_with(|| free_vars(m0).into_iter().collect());
+                let mut local = HashMap::default();
+                for y in ys.iter() {
+                    local.insert(y.clone(), env.get(y).unwrap().clone());
+                }
+                Control::Return(Value::Clo(Rc::new(Closure {
+                    env: local,
+                    func: f.clone(),
+                    param: x.clone(),
+                    body: m,
+                })))
+            }
+            App(m, n, _) => {
+                stack.push(Frame::Arg(delay(&env, n)));
+                Control::Eval(m, env)
+            }
+            LetIn(x, m, n) => {
+                let m = delay(&env, m);
+                Control::Eval(n, extend(&env, x, m))
+            }
+            Ifte(m, n1, n2) => {
+                stack.push(Frame::Ifte(n1, n2, env.clone()));
+                Control::Eval(m, env)
+            }
+            Raise(m, pos) => {
+                stack.push(Frame::Raise(*pos));
+                Control::Eval(m, env)
+            }
+            Try(m, x, n) => {
+                stack.push(Frame::Try(x, n, env.clone()));
+                Control::Eval(m, env)
+            }
+            Effect(_, m) => Control::Eval(m, env),
+            Perform(op, _, pos) => Control::Throw(Error::Unhandled(op.to_string(), *pos).into()),
+            Handle(..) => unsupported("effect handlers under lazy evaluation"),
+            CallCC(..) => unsupported("callcc under lazy evaluation"),
+        }
+    }
+
+    fn step_return(&mut self, frame: Frame<'a>, v: Value<'a>) -> Control<'a> {
+        match frame {
+            Frame::Op1(op1, pos) => {
+                self.ops += 1;
+                match eval_op1(&op1, &v, pos) {
+                    Ok(v) => Control::Return(v),
+                    Err(e) => Control::Throw(e.into()),
+                }
+            }
+            Frame::Op2L(op2, n, env, pos) => match short_circuit(&op2, &v) {
+                Some(v) => {
+                    self.ops += 1;
+                    Control::Return(v)
+                }
+                None => {
+                    self.stack.push(Frame::Op2R(op2, v, pos));
+                    Control::Eval(n, env)
+                }
+            },
+            Frame::Op2R(op2, m, pos) => {
+                self.ops += 1;
+                match eval_op2(&op2, &m, &v, pos) {
+                    Ok(v) => Control::Return(v),
+                    Err(e) => Control::Throw(e.into()),
+                }
+            }
+            Frame::Arg(n) => match &v {
+                Value::Clo(clo) => {
+                    self.betas += 1;
+                    let mut env = clo.env.clone();
+                    env.insert(clo.func.clone(), v.clone());
+                    env.insert(clo.param.clone(), n);
+                    Control::Eval(clo.body, Rc::new(env))
+                }
+                _ => panic!("eval_App({:?})", v),
+            },
+            Frame::Ifte(n1, n2, env) => match v {
+                Value::Bool(true) => Control::Eval(n1, env),
+                Value::Bool(false) => Control::Eval(n2, env),
+                _ => panic!("eval_Ifte({:?})", v),
+            },
+            Frame::Raise(pos) => Control::Throw(Exn::Raise(v, pos)),
+            Frame::Try(..) => Control::Return(v),
+            Frame::Update(t) => {
+                *t.borrow_mut() = Thunk::Forced(v.clone());
+                Control::Return(v)
+            }
+        }
+    }
+}
+
+fn unsupported<'a>(what: &str) -> Control<'a> {
+    Control::Throw(Error::Unsupported(String::from(what)).into())
+}
+
+pub fn eval<'a>(env: &Env<'a>, m: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
+    Machine::new(env.clone(), m, true).run()
+}
+
+pub fn eval_by_name<'a>(env: &Env<'a>, m: &'a Term) -> Result<Value<'a>, Exn<Value<'a>>> {
+    Machine::new(env.clone(), m, false).run()
+}
@@ -0,0 +1,25 @@
+pub mod ast0;
+pub mod ast1;
+pub mod ast2;
+pub mod ast3;
+pub mod ast4;
+pub mod ast5;
+pub mod ast6;
+pub mod cek;
+pub mod debug;
+pub mod error;
+pub mod eval;
+pub mod inline;
+pub mod lazy;
+pub mod names;
+pub mod ops;
+pub mod opt;
+pub mod parse;
+pub mod profile;
+pub mod smallstep;
+pub mod trans01;
+pub mod trans02;
+pub mod trans13;
+pub mod trans14;
+pub mod trans15;
+pub mod trans16;
@@ -1,32 +1,210 @@
-mod ast0;
-mod ast1;
-mod eval;
-mod names;
-mod parse;
-mod trans01;
-
 use ahash::HashMap;
 use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-use eval::*;
-use parse::*;
-use pest::Parser;
-use std::{cell::RefCell, fs};
+use rust_test::{
+    ast1, ast3, ast5, ast6, cek,
+    debug::Debugger,
+    error::{Error, Exn},
+    eval::*,
+    lazy, opt,
+    parse::*,
+    profile::Profiler,
+    smallstep, trans01, trans13, trans15, trans16,
+};
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    rc::Rc,
+};
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let file = fs::read_to_string(&args[1]).expect("cannot read file");
-    match LamParser::parse(Rule::prog, &file) {
-        Ok(mut pairs) => {
-            let tm = parse_term(pairs.next().unwrap().into_inner());
-            let tm = trans01::trans(&mut HashMap::default(), tm.as_ref());
-            let val = eval(&RefCell::new(HashMap::default()), tm.as_ref());
-            println!("value : {:?}", val);
+    let mut backend = String::from("eval");
+    let mut strategy = String::from("strict");
+    let mut report = false;
+    let mut trace = false;
+    let mut max_steps = 100_000_000;
+    let mut debug = false;
+    let mut opt_level = 0;
+    let mut dump_opt = false;
+    let mut profile = None;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        if arg == "debug" {
+            debug = true
+        } else if arg == "--report" {
+            report = true
+        } else if arg == "--profile" {
+            profile = Some(String::from("profile.folded"))
+        } else if let Some(p) = arg.strip_prefix("--profile=") {
+            profile = Some(String::from(p))
+        } else if arg == "--trace" {
+            trace = true
+        } else if let Some(n) = arg.strip_prefix("--max-steps=") {
+            max_steps = n.parse().expect("--max-steps expects a number")
+        } else if let Some(n) = arg.strip_prefix("--opt-level=") {
+            opt_level = n.parse().expect("--opt-level expects a number")
+        } else if arg == "--dump-opt" {
+            dump_opt = true
+        } else if let Some(b) = arg.strip_prefix("--backend=") {
+            backend = String::from(b)
+        } else if let Some(s) = arg.strip_prefix("--strategy=") {
+            strategy = String::from(s)
+        } else {
+            path = Some(arg)
+        }
+    }
+    let file = fs::read_to_string(path.expect("no input file")).expect("cannot read file");
+    match parse_prog(&file) {
+        Ok(tm) => {
+            let tm = trans01::trans(&mut trans01::Ctx::default(), tm.as_ref());
+            let tm = opt::optimize(tm.as_ref(), opt_level);
+            if dump_opt {
+                return println!("{}", tm);
+            }
+            if report {
+                return print_report(tm.as_ref(), max_steps);
+            }
+            if let Some(out) = profile {
+                return print_profile(tm.as_ref(), max_steps, &out);
+            }
+            if debug {
+                return run_debugger(tm.as_ref());
+            }
+            if trace {
+                return print_trace(tm.as_ref(), max_steps);
+            }
+            let result = match (strategy.as_str(), backend.as_str()) {
+                ("strict", "eval") => eval(&RefCell::new(HashMap::default()), tm.as_ref()),
+                ("strict", "cek") => cek::run(Rc::new(HashMap::default()), tm.as_ref()),
+                ("strict", "closure") => {
+                    let result = trans13::trans(tm.as_ref()).map_err(Exn::Error);
+                    match result.and_then(|prog| ast3::run(&prog)) {
+                        Ok(val) => println!("value : {:?}", val),
+                        Err(e) => eprintln!("runtime error : {}", Error::from(e)),
+                    }
+                    return;
+                }
+                ("strict", "cps") => {
+                    let tm = match trans15::trans(tm.as_ref()) {
+                        Ok(tm) => tm,
+                        Err(e) => return eprintln!("runtime error : {}", e),
+                    };
+                    match ast5::run(&tm) {
+                        Ok(val) => println!("value : {:?}", val),
+                        Err(e) => eprintln!("runtime error : {}", Error::from(e)),
+                    }
+                    return;
+                }
+                ("strict", "defun") => {
+                    let result = trans16::trans(tm.as_ref()).map_err(Exn::Error);
+                    match result.and_then(|prog| ast6::run(&prog)) {
+                        Ok(val) => println!("value : {:?}", val),
+                        Err(e) => eprintln!("runtime error : {}", Error::from(e)),
+                    }
+                    return;
+                }
+                ("lazy", "eval") => lazy::eval(&Rc::new(HashMap::default()), tm.as_ref()),
+                ("name", "eval") => lazy::eval_by_name(&Rc::new(HashMap::default()), tm.as_ref()),
+                _ => {
+                    eprintln!(
+                        "usage: no --backend={} for --strategy={}, strict runs on \
+                         eval, cek, closure, cps or defun, lazy and name on eval",
+                        backend, strategy
+                    );
+                    std::process::exit(2)
+                }
+            };
+            match result {
+                Ok(val) => println!("value : {:?}", val),
+                Err(e) => eprintln!("runtime error : {}", Error::from(e)),
+            }
         }
         Err(e) => {
             eprintln!("Parse failed: {:?}", e)
         }
     }
 }
+
+fn run_debugger(tm: &ast1::Term) {
+    let mut debugger = Debugger::new(tm);
+    println!("{}", debugger.command("where"));
+    let mut line = String::new();
+    loop {
+        print!("(debug) ");
+        io::stdout().flush().unwrap();
+        line.clear();
+        if io::stdin().read_line(&mut line).unwrap() == 0 || line.trim() == "quit" {
+            return;
+        }
+        println!("{}", debugger.command(&line));
+    }
+}
+
+fn print_trace(tm: &ast1::Term, max_steps: usize) {
+    let mut m = Box::new(tm.clone());
+    for i in 0.. {
+        println!("{:>6}  {}", i, m);
+        if i == max_steps {
+            return println!("stopped, step limit reached");
+        }
+        match smallstep::step(&m) {
+            Ok(Some(m1)) => m = m1,
+            Ok(None) => match *m {
+                ast1::Term::Raise(v, pos) => {
+                    return eprintln!("runtime error : {}", Error::Uncaught(v.to_string(), pos));
+                }
+                _ => return,
+            },
+            Err(e) => return eprintln!("runtime error : {}", e),
+        }
+    }
+}
+
+fn print_profile(tm: &ast1::Term, max_steps: usize, out: &str) {
+    let mut profiler = Profiler::new(tm);
+    match profiler.run(max_steps) {
+        Some(Ok(val)) => println!("value : {:?}", val),
+        Some(Err(e)) => eprintln!("runtime error : {}", Error::from(e)),
+        None => println!("stopped, step limit reached"),
+    }
+    println!(
+        "{:<20} {:>12} {:>12} {:>12}",
+        "function", "calls", "self", "total"
+    );
+    for row in profiler.table() {
+        println!(
+            "{:<20} {:>12} {:>12} {:>12}",
+            row.name, row.calls, row.self_steps, row.total_steps
+        );
+    }
+    fs::write(out, profiler.folded()).expect("cannot write profile");
+    println!("folded stacks written to {}", out);
+}
+
+// every strategy is cut off after max_steps machine transitions, test2.txt
+// called by name recomputes its arguments exponentially often and is stopped
+// there, and a strategy that cannot run the program gets an unsupported row
+fn print_report(tm: &ast1::Term, max_steps: usize) {
+    println!("{:<8} {:>12} {:>12}  result", "strategy", "beta", "ops");
+    let mut machine = cek::Machine::new(Rc::new(HashMap::default()), tm);
+    let result = (0..max_steps).find_map(|_| machine.step());
+    print_row("strict", machine.betas, machine.ops, result);
+    for (strategy, memo) in [("lazy", true), ("name", false)] {
+        let mut machine = lazy::Machine::new(Rc::new(HashMap::default()), tm, memo);
+        let result = (0..max_steps).find_map(|_| machine.step());
+        print_row(strategy, machine.betas, machine.ops, result);
+    }
+}
+
+fn print_row(strategy: &str, betas: usize, ops: usize, result: Option<Result<Value, Exn<Value>>>) {
+    let result = match result {
+        Some(Ok(val)) => format!("{:?}", val),
+        Some(Err(Exn::Error(Error::Unsupported(what)))) => format!("unsupported, {}", what),
+        Some(Err(e)) => format!("runtime error : {}", Error::from(e)),
+        None => String::from("stopped, step limit reached"),
+    };
+    println!("{:<8} {:>12} {:>12}  {}", strategy, betas, ops, result);
+}
@@ -1,12 +1,14 @@
 use std::{
     cmp::Ordering,
+    fmt::{self, Display},
     hash::{Hash, Hasher},
+    rc::Rc,
     sync::atomic::{AtomicI32, Ordering::Relaxed},
 };
 
 #[derive(Debug, Clone)]
 pub struct Name {
-    name: String,
+    name: Rc<str>,
     id: i32,
 }
 
@@ -15,10 +17,25 @@ static STAMP: AtomicI32 = AtomicI32::new(0);
 impl Name {
     pub fn new(s: String) -> Self {
         Name {
-            name: s,
+            name: s.into(),
             id: STAMP.fetch_add(1, Relaxed),
         }
     }
+
+    // a new name for a renamed binder, the prime tells it apart from the
+    // name it replaces when printed
+    pub fn fresh(&self) -> Self {
+        match self.name.is_empty() {
+            true => Name::new(String::new()),
+            false => Name::new(format!("{}'", self.name)),
+        }
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
 }
 
 impl Hash for Name {
@@ -29,7 +46,7 @@ impl Hash for Name {
 
 impl PartialOrd for Name {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
     }
 }
 
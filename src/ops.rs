@@ -0,0 +1,118 @@
+use crate::{
+    ast1::{Op1, Op2},
+    error::{Error, Pos},
+};
+use std::{
+    cell::RefCell,
+    cmp::Ordering::{self, *},
+    fmt::Debug,
+    rc::Rc,
+};
+
+// the primitive operations, shared by every backend whose values implement
+// Prim
+pub trait Prim: Clone + Debug {
+    fn int(i: i32) -> Self;
+    fn float(f: f64) -> Self;
+    fn bool(b: bool) -> Self;
+    fn reference(r: Rc<RefCell<Self>>) -> Self;
+    fn view(&self) -> View<'_, Self>;
+}
+
+// what an operation can see of a value
+pub enum View<'v, V> {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Ref(&'v RefCell<V>),
+    Fun,
+    Other,
+}
+
+pub fn eval_op1<V: Prim>(op: &Op1, m: &V, pos: Pos) -> Result<V, Error> {
+    use self::Op1::*;
+    use View::*;
+    match (op, m.view()) {
+        (Not, Bool(b)) => Ok(V::bool(!b)),
+        (Neg, Int(i)) => i.checked_neg().map(V::int).ok_or(Error::Overflow(pos)),
+        (Neg, Float(f)) => Ok(V::float(-f)),
+        // saturating truncation toward zero, NaN maps to 0
+        (IntOfFloat, Float(f)) => Ok(V::int(f as i32)),
+        (FloatOfInt, Int(i)) => Ok(V::float(i as f64)),
+        (MkRef, _) => Ok(V::reference(Rc::new(RefCell::new(m.clone())))),
+        (Deref, Ref(r)) => Ok(r.borrow().clone()),
+        (_, _) => panic!("eval_op1({:?}, {:?})", op, m),
+    }
+}
+
+pub fn short_circuit<V: Prim>(op: &Op2, m: &V) -> Option<V> {
+    match (op, m.view()) {
+        (Op2::And, View::Bool(false)) => Some(V::bool(false)),
+        (Op2::Or, View::Bool(true)) => Some(V::bool(true)),
+        (_, _) => None,
+    }
+}
+
+pub fn compare<V: Prim>(m: &V, n: &V, pos: Pos) -> Result<Option<Ordering>, Error> {
+    compare_refs(m, n, pos, &mut vec![])
+}
+
+// the pairs of references whose contents are being compared, meeting one
+// again means a reference holds itself and the pair is taken as equal
+type Seen<V> = Vec<(*const RefCell<V>, *const RefCell<V>)>;
+
+fn compare_refs<V: Prim>(
+    m: &V,
+    n: &V,
+    pos: Pos,
+    seen: &mut Seen<V>,
+) -> Result<Option<Ordering>, Error> {
+    use View::*;
+    match (m.view(), n.view()) {
+        (Int(i), Int(j)) => Ok(Some(i.cmp(&j))),
+        (Float(i), Float(j)) => Ok(i.partial_cmp(&j)),
+        (Bool(i), Bool(j)) => Ok(Some(i.cmp(&j))),
+        (Ref(r), Ref(s)) if seen.contains(&(r as *const _, s as *const _)) => Ok(Some(Equal)),
+        (Ref(r), Ref(s)) => {
+            seen.push((r, s));
+            compare_refs(&*r.borrow(), &*s.borrow(), pos, seen)
+        }
+        (Fun, _) | (_, Fun) => Err(Error::FunctionalValue(pos)),
+        (_, _) => Err(Error::Incomparable(pos)),
+    }
+}
+
+pub fn eval_op2<V: Prim>(op: &Op2, m: &V, n: &V, pos: Pos) -> Result<V, Error> {
+    use self::Op2::*;
+    use View::*;
+    match (op, m.view(), n.view()) {
+        (Add, Int(i), Int(j)) => i.checked_add(j).map(V::int).ok_or(Error::Overflow(pos)),
+        (Sub, Int(i), Int(j)) => i.checked_sub(j).map(V::int).ok_or(Error::Overflow(pos)),
+        (Mul, Int(i), Int(j)) => i.checked_mul(j).map(V::int).ok_or(Error::Overflow(pos)),
+        (Div, Int(_), Int(0)) => Err(Error::DivByZero(pos)),
+        (Div, Int(i), Int(j)) => i.checked_div(j).map(V::int).ok_or(Error::Overflow(pos)),
+        (Mod, Int(_), Int(0)) => Err(Error::DivByZero(pos)),
+        (Mod, Int(i), Int(j)) => i.checked_rem(j).map(V::int).ok_or(Error::Overflow(pos)),
+        (Add, Float(i), Float(j)) => Ok(V::float(i + j)),
+        (Sub, Float(i), Float(j)) => Ok(V::float(i - j)),
+        (Mul, Float(i), Float(j)) => Ok(V::float(i * j)),
+        (Div, Float(i), Float(j)) => Ok(V::float(i / j)),
+        (Mod, Float(i), Float(j)) => Ok(V::float(i % j)),
+        (Lte, _, _) => Ok(V::bool(matches!(compare(m, n, pos)?, Some(Less | Equal)))),
+        (Gte, _, _) => Ok(V::bool(matches!(
+            compare(m, n, pos)?,
+            Some(Greater | Equal)
+        ))),
+        (Lt, _, _) => Ok(V::bool(compare(m, n, pos)? == Some(Less))),
+        (Gt, _, _) => Ok(V::bool(compare(m, n, pos)? == Some(Greater))),
+        (Eq, _, _) => Ok(V::bool(compare(m, n, pos)? == Some(Equal))),
+        (Neq, _, _) => Ok(V::bool(compare(m, n, pos)? != Some(Equal))),
+        (And, Bool(i), Bool(j)) => Ok(V::bool(i && j)),
+        (Or, Bool(i), Bool(j)) => Ok(V::bool(i || j)),
+        (Assign, Ref(r), _) => {
+            *r.borrow_mut() = n.clone();
+            Ok(n.clone())
+        }
+        (_, _, _) => panic!("eval_op2({:?}, {:?}, {:?})", op, m, n),
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    ast1::*,
+    error::Pos,
+    eval::Value,
+    inline::inline,
+    ops::{eval_op1, eval_op2},
+};
+
+// level 0 leaves the term alone, level 1 folds constants and simplifies,
+// each level above that adds a round of inlining of small functions
+pub fn optimize(m: &Term, level: u32) -> Box<Term> {
+    match level {
+        0 => Box::new(m.clone()),
+        _ => (1..level).fold(simplify(m), |m, _| simplify(&inline(&m))),
+    }
+}
+
+// one bottom up pass, the subterms of a term are simplified before it
+pub fn simplify(m0: &Term) -> Box<Term> {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => Box::new(m0.clone()),
+        Op1(op, m, pos) => simplify_op1(*op, *simplify(m), *pos),
+        Op2(op, m, n, pos) => simplify_op2(*op, simplify(m), simplify(n), *pos),
+        Fun(f, x, m) => fun(f.clone(), x.clone(), simplify(m)),
+        App(m, n, pos) => app(simplify(m), simplify(n), *pos),
+        LetIn(x, m, n) => {
+            let m = simplify(m);
+            let n = simplify(n);
+            match pure(&m) && !free_vars(&n).contains(x) {
+                true => n,
+                false => letin(x.clone(), m, n),
+            }
+        }
+        Ifte(m, n1, n2) => match *simplify(m) {
+            Bool(true) => simplify(n1),
+            Bool(false) => simplify(n2),
+            m => ifte(Box::new(m), simplify(n1), simplify(n2)),
+        },
+        Raise(m, pos) => raise(simplify(m), *pos),
+        Try(m, x, n) => trywith(simplify(m), x.clone(), simplify(n)),
+        Effect(op, m) => effect(op.clone(), simplify(m)),
+        Perform(op, m, pos) => perform(op.clone(), simplify(m), *pos),
+        Handle(m, x, n, cases) => handle(
+            simplify(m),
+            x.clone(),
+            simplify(n),
+            cases
+                .iter()
+                .map(|(op, y, k, m)| (op.clone(), y.clone(), k.clone(), simplify(m)))
+                .collect(),
+        ),
+        CallCC(m, pos) => callcc(simplify(m), *pos),
+    }
+}
+
+fn simplify_op1(op: Op1, m: Term, pos: Pos) -> Box<Term> {
+    use self::Op1::*;
+    use Term::{Bool, Float, Int};
+    match (op, m) {
+        (Not, Term::Op1(Not, m, _)) if bool_valued(&m) => m,
+        (Not, m @ Bool(_))
+        | (Neg, m @ (Int(_) | Float(_)))
+        | (IntOfFloat, m @ Float(_))
+        | (FloatOfInt, m @ Int(_)) => {
+            // -i32::MIN is left to overflow at run time
+            match eval_op1(&op, &value(&m).unwrap(), pos) {
+                Ok(v) => literal(v),
+                Err(_) => op1(op, Box::new(m), pos),
+            }
+        }
+        (op, m) => op1(op, Box::new(m), pos),
+    }
+}
+
+fn simplify_op2(op: Op2, m: Box<Term>, n: Box<Term>, pos: Pos) -> Box<Term> {
+    use Op2::*;
+    use Term::{Bool, Float, Int};
+    match (op, m.as_ref(), n.as_ref()) {
+        (Add | Sub | Mul | Div | Mod, Int(_), Int(_))
+        | (Add | Sub | Mul | Div | Mod, Float(_), Float(_))
+        | (Lte | Gte | Lt | Gt | Eq | Neq, Int(_), Int(_))
+        | (Lte | Gte | Lt | Gt | Eq | Neq, Float(_), Float(_))
+        | (Lte | Gte | Lt | Gt | Eq | Neq | And | Or, Bool(_), Bool(_)) => {
+            // a division by zero is left to fail at run time
+            match eval_op2(&op, &value(&m).unwrap(), &value(&n).unwrap(), pos) {
+                Ok(v) => literal(v),
+                Err(_) => op2(op, m, n, pos),
+            }
+        }
+        // an identity only holds when the other operand is of its type,
+        // 1.5 + 0 fails at run time
+        (Add | Sub, _, Int(0)) | (Mul | Div, _, Int(1)) if int_valued(&m) => m,
+        (And, _, Bool(true)) | (Or, _, Bool(false)) if bool_valued(&m) => m,
+        (Add, Int(0), _) | (Mul, Int(1), _) if int_valued(&n) => n,
+        (And, Bool(true), _) | (Or, Bool(false), _) if bool_valued(&n) => n,
+        (And, Bool(false), _) | (Or, Bool(true), _) => m,
+        _ => op2(op, m, n, pos),
+    }
+}
+
+// m is an int whenever its evaluation succeeds
+fn int_valued(m0: &Term) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Op1(self::Op1::IntOfFloat, ..) => true,
+        Op1(self::Op1::Neg, m, _) => int_valued(m),
+        Op2(
+            self::Op2::Add | self::Op2::Sub | self::Op2::Mul | self::Op2::Div | self::Op2::Mod,
+            m,
+            n,
+            _,
+        ) => int_valued(m) || int_valued(n),
+        _ => false,
+    }
+}
+
+// m is a bool whenever its evaluation succeeds
+fn bool_valued(m0: &Term) -> bool {
+    use self::Op2::*;
+    use Term::*;
+    matches!(
+        m0,
+        Bool(_) | Op1(self::Op1::Not, ..) | Op2(Lte | Gte | Lt | Gt | Eq | Neq | And | Or, ..)
+    )
+}
+
+fn value<'a>(m: &Term) -> Option<Value<'a>> {
+    match m {
+        Term::Int(i) => Some(Value::Int(*i)),
+        Term::Float(f) => Some(Value::Float(*f)),
+        Term::Bool(b) => Some(Value::Bool(*b)),
+        _ => None,
+    }
+}
+
+fn literal(v: Value) -> Box<Term> {
+    match v {
+        Value::Int(i) => int(i),
+        Value::Float(f) => float(f),
+        Value::Bool(b) => bool(b),
+        _ => panic!("literal({:?})", v),
+    }
+}
+
+// evaluating m cannot raise, fail, loop or change the store, so it can be
+// dropped when its value is not used
+pub fn pure(m0: &Term) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) | Fun(..) => true,
+        // an operation on an operand of the wrong type fails, and
+        // arithmetic on ints can overflow
+        Op1(self::Op1::Not, m, _) => bool_valued(m) && pure(m),
+        Op1(self::Op1::FloatOfInt, m, _) => int_valued(m) && pure(m),
+        Op2(self::Op2::And | self::Op2::Or, m, n, _) => {
+            bool_valued(m) && bool_valued(n) && pure(m) && pure(n)
+        }
+        LetIn(_, m, n) => pure(m) && pure(n),
+        _ => false,
+    }
+}
@@ -1,6 +1,8 @@
-use crate::ast0;
+use crate::{ast0, error::Pos};
 use ast0::*;
-use pest::iterators::Pairs;
+use pest::Parser;
+use pest::error::ErrorVariant;
+use pest::iterators::{Pair, Pairs};
 use pest::pratt_parser::PrattParser;
 
 #[derive(pest_derive::Parser)]
@@ -12,92 +14,195 @@ lazy_static::lazy_static! {
     use pest::pratt_parser::{Assoc::*, Op};
     use Rule::*;
     PrattParser::new()
+      .op(Op::infix(assign, Right))
       .op(Op::infix(and, Left) | Op::infix(or, Left))
       .op(Op::infix(eq, Left) | Op::infix(neq, Left))
       .op(Op::infix(lte, Left) | Op::infix(gte, Left) | Op::infix(lt, Left) | Op::infix(gt, Left))
       .op(Op::infix(add, Left) | Op::infix(sub, Left))
-      .op(Op::infix(mul, Left) | Op::infix(div, Left))
+      .op(Op::infix(mul, Left) | Op::infix(div, Left) | Op::infix(modulo, Left))
       .op(Op::prefix(neg) | Op::prefix(not))
       .op(Op::infix(app, Left))
   };
 }
 
-pub fn parse_term(pairs: Pairs<Rule>) -> Box<Term> {
+fn parse_primary(primary: Pair<Rule>) -> Box<Term> {
     use self::Op1::*;
-    use self::Op2::*;
-    PRATT_PARSER
-        .map_primary(|primary| match primary.as_rule() {
-            Rule::bool => bool(primary.as_str().parse::<bool>().unwrap()),
-            Rule::integer => int(primary.as_str().parse::<i32>().unwrap()),
-            Rule::var => var(String::from(primary.as_str())),
-            Rule::letin => {
-                let outer = primary.into_inner().next().unwrap();
-                match outer.as_rule() {
-                    Rule::decl_rec => {
-                        let mut inner = outer.into_inner();
-                        let f = String::from(inner.next().unwrap().as_str());
-                        let x = String::from(inner.next().unwrap().as_str());
-                        let args = inner.next().unwrap().into_inner();
-                        let mut body = parse_term(inner.next().unwrap().into_inner());
-                        let m = parse_term(inner.next().unwrap().into_inner());
-                        for arg in args.rev() {
-                            body = fun(String::from(""), String::from(arg.as_str()), body)
-                        }
-                        letin(f.clone(), fun(f, x, body), m)
+    match primary.as_rule() {
+        Rule::bool => bool(primary.as_str().parse::<bool>().unwrap()),
+        Rule::integer => int(primary.as_str().parse::<i32>().unwrap()),
+        Rule::float => float(primary.as_str().parse::<f64>().unwrap()),
+        Rule::var => var(String::from(primary.as_str())),
+        Rule::letin => {
+            let outer = primary.into_inner().next().unwrap();
+            match outer.as_rule() {
+                Rule::decl_rec => {
+                    let mut inner = outer.into_inner();
+                    let f = String::from(inner.next().unwrap().as_str());
+                    let x = String::from(inner.next().unwrap().as_str());
+                    let args = inner.next().unwrap().into_inner();
+                    let mut body = parse_term(inner.next().unwrap().into_inner());
+                    let m = parse_term(inner.next().unwrap().into_inner());
+                    for arg in args.rev() {
+                        body = fun(String::from(""), String::from(arg.as_str()), body)
                     }
-                    Rule::decl => {
-                        let mut inner = outer.into_inner();
-                        let x = String::from(inner.next().unwrap().as_str());
-                        let args = inner.next().unwrap().into_inner();
-                        let mut body = parse_term(inner.next().unwrap().into_inner());
-                        let m = parse_term(inner.next().unwrap().into_inner());
-                        for arg in args.rev() {
-                            body = fun(String::from(""), String::from(arg.as_str()), body)
-                        }
-                        letin(x, body, m)
+                    letin(f.clone(), fun(f, x, body), m)
+                }
+                Rule::decl => {
+                    let mut inner = outer.into_inner();
+                    let x = String::from(inner.next().unwrap().as_str());
+                    let args = inner.next().unwrap().into_inner();
+                    let mut body = parse_term(inner.next().unwrap().into_inner());
+                    let m = parse_term(inner.next().unwrap().into_inner());
+                    for arg in args.rev() {
+                        body = fun(String::from(""), String::from(arg.as_str()), body)
                     }
-                    _ => panic!(),
+                    letin(x, body, m)
                 }
+                rule => unreachable!("letin({:?})", rule),
             }
-            Rule::lambda => {
-                let mut inner = primary.into_inner();
-                let args = inner.next().unwrap().into_inner();
-                let mut body = parse_term(inner.next().unwrap().into_inner());
-                for arg in args.rev() {
-                    body = fun(String::from(""), String::from(arg.as_str()), body)
+        }
+        Rule::lambda => {
+            let mut inner = primary.into_inner();
+            let args = inner.next().unwrap().into_inner();
+            let mut body = parse_term(inner.next().unwrap().into_inner());
+            for arg in args.rev() {
+                body = fun(String::from(""), String::from(arg.as_str()), body)
+            }
+            body
+        }
+        Rule::ifte => {
+            let mut inner = primary.into_inner();
+            let cond = parse_term(inner.next().unwrap().into_inner());
+            let m1 = parse_term(inner.next().unwrap().into_inner());
+            let m2 = parse_term(inner.next().unwrap().into_inner());
+            ifte(cond, m1, m2)
+        }
+        Rule::raise => {
+            let (line, col) = primary.line_col();
+            let m = parse_primary(primary.into_inner().next().unwrap());
+            raise(m, Pos { line, col })
+        }
+        Rule::trywith => {
+            let mut inner = primary.into_inner();
+            let m = parse_term(inner.next().unwrap().into_inner());
+            let x = String::from(inner.next().unwrap().as_str());
+            let n = parse_term(inner.next().unwrap().into_inner());
+            trywith(m, x, n)
+        }
+        Rule::effect => {
+            let mut inner = primary.into_inner();
+            let op = String::from(inner.next().unwrap().as_str());
+            let m = parse_term(inner.next().unwrap().into_inner());
+            effect(op, m)
+        }
+        Rule::perform => {
+            let (line, col) = primary.line_col();
+            let mut inner = primary.into_inner();
+            let op = String::from(inner.next().unwrap().as_str());
+            let m = parse_primary(inner.next().unwrap());
+            perform(op, m, Pos { line, col })
+        }
+        Rule::handle => {
+            let mut inner = primary.into_inner();
+            let m = parse_term(inner.next().unwrap().into_inner());
+            let mut ret = (String::from("x"), var(String::from("x")));
+            let mut cases = vec![];
+            for case in inner {
+                match case.as_rule() {
+                    Rule::ret_case => {
+                        let mut inner = case.into_inner();
+                        let x = String::from(inner.next().unwrap().as_str());
+                        let n = parse_term(inner.next().unwrap().into_inner());
+                        ret = (x, n)
+                    }
+                    Rule::op_case => {
+                        let mut inner = case.into_inner();
+                        let op = String::from(inner.next().unwrap().as_str());
+                        let x = String::from(inner.next().unwrap().as_str());
+                        let k = String::from(inner.next().unwrap().as_str());
+                        let n = parse_term(inner.next().unwrap().into_inner());
+                        cases.push((op, x, k, n))
+                    }
+                    rule => unreachable!("handle({:?})", rule),
                 }
-                body
             }
-            Rule::ifte => {
-                let mut inner = primary.into_inner();
-                let cond = parse_term(inner.next().unwrap().into_inner());
-                let m1 = parse_term(inner.next().unwrap().into_inner());
-                let m2 = parse_term(inner.next().unwrap().into_inner());
-                ifte(cond, m1, m2)
+            handle(m, ret.0, ret.1, cases)
+        }
+        Rule::callcc => {
+            let (line, col) = primary.line_col();
+            let m = parse_primary(primary.into_inner().next().unwrap());
+            callcc(m, Pos { line, col })
+        }
+        Rule::prim => {
+            let (line, col) = primary.line_col();
+            let mut inner = primary.into_inner();
+            let op = match inner.next().unwrap().as_rule() {
+                Rule::int_of_float => IntOfFloat,
+                Rule::float_of_int => FloatOfInt,
+                Rule::mkref => MkRef,
+                Rule::deref => Deref,
+                rule => unreachable!("prim({:?})", rule),
+            };
+            op1(op, parse_primary(inner.next().unwrap()), Pos { line, col })
+        }
+        Rule::term => parse_term(primary.into_inner()),
+        rule => unreachable!("parse_primary({:?})", rule),
+    }
+}
+
+pub fn parse_term(pairs: Pairs<Rule>) -> Box<Term> {
+    use self::Op1::*;
+    use self::Op2::*;
+    PRATT_PARSER
+        .map_primary(parse_primary)
+        .map_infix(|lhs, op, rhs| {
+            let (line, col) = op.line_col();
+            let pos = Pos { line, col };
+            match op.as_rule() {
+                Rule::add => op2(Add, lhs, rhs, pos),
+                Rule::sub => op2(Sub, lhs, rhs, pos),
+                Rule::mul => op2(Mul, lhs, rhs, pos),
+                Rule::div => op2(Div, lhs, rhs, pos),
+                Rule::modulo => op2(Mod, lhs, rhs, pos),
+                Rule::lte => op2(Lte, lhs, rhs, pos),
+                Rule::gte => op2(Gte, lhs, rhs, pos),
+                Rule::lt => op2(Lt, lhs, rhs, pos),
+                Rule::gt => op2(Gt, lhs, rhs, pos),
+                Rule::eq => op2(Eq, lhs, rhs, pos),
+                Rule::neq => op2(Neq, lhs, rhs, pos),
+                Rule::and => op2(And, lhs, rhs, pos),
+                Rule::or => op2(Or, lhs, rhs, pos),
+                Rule::assign => op2(Assign, lhs, rhs, pos),
+                Rule::app => app(lhs, rhs, pos),
+                rule => unreachable!("infix({:?})", rule),
             }
-            Rule::term => parse_term(primary.into_inner()),
-            _ => panic!(),
-        })
-        .map_infix(|lhs, op, rhs| match op.as_rule() {
-            Rule::add => op2(Add, lhs, rhs),
-            Rule::sub => op2(Sub, lhs, rhs),
-            Rule::mul => op2(Mul, lhs, rhs),
-            Rule::div => op2(Div, lhs, rhs),
-            Rule::lte => op2(Lte, lhs, rhs),
-            Rule::gte => op2(Gte, lhs, rhs),
-            Rule::lt => op2(Lt, lhs, rhs),
-            Rule::gt => op2(Gt, lhs, rhs),
-            Rule::eq => op2(Eq, lhs, rhs),
-            Rule::neq => op2(Neq, lhs, rhs),
-            Rule::and => op2(And, lhs, rhs),
-            Rule::or => op2(Or, lhs, rhs),
-            Rule::app => app(lhs, rhs),
-            _ => panic!(),
         })
-        .map_prefix(|op, rhs| match op.as_rule() {
-            Rule::not => op1(Not, rhs),
-            Rule::neg => op1(Neg, rhs),
-            _ => panic!(),
+        .map_prefix(|op, rhs| {
+            let (line, col) = op.line_col();
+            let pos = Pos { line, col };
+            match op.as_rule() {
+                Rule::not => op1(Not, rhs, pos),
+                Rule::neg => op1(Neg, rhs, pos),
+                rule => unreachable!("prefix({:?})", rule),
+            }
         })
         .parse(pairs)
 }
+
+pub fn parse_prog(src: &str) -> Result<Box<Term>, Box<pest::error::Error<Rule>>> {
+    let mut pairs = LamParser::parse(Rule::prog, src)?;
+    // the only token the grammar accepts but parse_primary cannot convert
+    if let Some(pair) = pairs
+        .clone()
+        .flatten()
+        .find(|pair| pair.as_rule() == Rule::integer && pair.as_str().parse::<i32>().is_err())
+    {
+        let message = format!("integer literal {} is out of range", pair.as_str());
+        let variant = ErrorVariant::CustomError { message };
+        return Err(Box::new(pest::error::Error::new_from_span(
+            variant,
+            pair.as_span(),
+        )));
+    }
+    Ok(parse_term(pairs.next().unwrap().into_inner()))
+}
@@ -0,0 +1,155 @@
+use crate::{ast1::Term, cek::Machine, debug::CallStack, error::Exn, eval::Value};
+use ahash::HashMap;
+use std::rc::Rc;
+
+// one call path, direct self-recursion stays on the same node
+struct Node {
+    name: String,
+    parent: usize,
+    children: HashMap<String, usize>,
+    steps: usize,
+}
+
+pub struct Row {
+    pub name: String,
+    pub calls: usize,
+    pub self_steps: usize,
+    pub total_steps: usize,
+}
+
+// every machine transition is charged to the innermost call in progress,
+// or to main outside of any function
+pub struct Profiler<'a> {
+    machine: Machine<'a>,
+    stack: CallStack<'a>,
+    nodes: Vec<Node>,
+    // call ids of stack.calls paired with their node
+    path: Vec<(usize, usize)>,
+    calls: HashMap<String, usize>,
+}
+
+impl<'a> Profiler<'a> {
+    pub fn new(m: &'a Term) -> Self {
+        Profiler {
+            machine: Machine::new(Rc::new(HashMap::default()), m),
+            stack: CallStack::new(m),
+            nodes: vec![Node {
+                name: String::from("main"),
+                parent: 0,
+                children: HashMap::default(),
+                steps: 0,
+            }],
+            path: vec![],
+            calls: HashMap::default(),
+        }
+    }
+
+    fn node(&self) -> usize {
+        self.path.last().map_or(0, |(_, node)| *node)
+    }
+
+    fn child(&mut self, parent: usize, name: &str) -> usize {
+        if self.nodes[parent].name == name && parent != 0 {
+            return parent;
+        }
+        if let Some(node) = self.nodes[parent].children.get(name) {
+            return *node;
+        }
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent,
+            children: HashMap::default(),
+            steps: 0,
+        });
+        self.nodes[parent].children.insert(name.to_string(), node);
+        node
+    }
+
+    pub fn run(&mut self, max_steps: usize) -> Option<Result<Value<'a>, Exn<Value<'a>>>> {
+        for _ in 0..max_steps {
+            let node = self.node();
+            self.nodes[node].steps += 1;
+            let entered = self.stack.step(&mut self.machine);
+            if let Some(result) = self.machine.result() {
+                return Some(result);
+            }
+            let calls = &self.stack.calls;
+            self.path.truncate(calls.len());
+            while let Some((id, _)) = self.path.last()
+                && *id != calls[self.path.len() - 1].id
+            {
+                self.path.pop();
+            }
+            for i in self.path.len()..self.stack.calls.len() {
+                let name = self.stack.calls[i].name.clone();
+                let node = self.node();
+                let node = self.child(node, &name);
+                self.path.push((self.stack.calls[i].id, node));
+            }
+            if entered {
+                let name = &self.stack.calls.last().unwrap().name;
+                *self.calls.entry(name.clone()).or_default() += 1;
+            }
+        }
+        None
+    }
+
+    // sorted by total steps, a function counts once per path however deep
+    // it recurses
+    pub fn table(&self) -> Vec<Row> {
+        let mut subtree: Vec<usize> = self.nodes.iter().map(|node| node.steps).collect();
+        for i in (1..self.nodes.len()).rev() {
+            subtree[self.nodes[i].parent] += subtree[i];
+        }
+        let mut rows: HashMap<&str, Row> = HashMap::default();
+        let mut on_path: HashMap<&str, usize> = HashMap::default();
+        let mut todo = vec![(0, true)];
+        while let Some((i, enter)) = todo.pop() {
+            let name = self.nodes[i].name.as_str();
+            if !enter {
+                *on_path.get_mut(name).unwrap() -= 1;
+                continue;
+            }
+            let row = rows.entry(name).or_insert_with(|| Row {
+                name: name.to_string(),
+                calls: self.calls.get(name).copied().unwrap_or(0),
+                self_steps: 0,
+                total_steps: 0,
+            });
+            row.self_steps += self.nodes[i].steps;
+            let depth = on_path.entry(name).or_default();
+            if *depth == 0 {
+                row.total_steps += subtree[i];
+            }
+            *depth += 1;
+            todo.push((i, false));
+            todo.extend(self.nodes[i].children.values().map(|j| (*j, true)));
+        }
+        let mut rows: Vec<Row> = rows.into_values().collect();
+        rows.sort_by(|a, b| {
+            (b.total_steps, b.self_steps, &a.name).cmp(&(a.total_steps, a.self_steps, &b.name))
+        });
+        rows
+    }
+
+    // one "main;f;g steps" line per call path, as read by flamegraph tools
+    pub fn folded(&self) -> String {
+        let mut lines = vec![];
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.steps == 0 {
+                continue;
+            }
+            let mut names = vec![node.name.as_str()];
+            let mut j = i;
+            while j != 0 {
+                j = self.nodes[j].parent;
+                names.push(self.nodes[j].name.as_str());
+            }
+            names.reverse();
+            lines.push(format!("{} {}", names.join(";"), node.steps));
+        }
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+}
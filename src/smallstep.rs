@@ -0,0 +1,124 @@
+use crate::{
+    ast1::*,
+    error::{Error, Exn},
+    eval::{Closure, Value},
+    ops::{eval_op1, eval_op2, short_circuit},
+};
+use ahash::HashMap;
+use std::rc::Rc;
+
+pub fn is_value(m: &Term) -> bool {
+    matches!(
+        m,
+        Term::Int(_) | Term::Float(_) | Term::Bool(_) | Term::Fun(..)
+    )
+}
+
+// primitive operations are delegated to eval through single use values
+fn to_value(m: &Term) -> Value<'_> {
+    match m {
+        Term::Int(i) => Value::Int(*i),
+        Term::Float(f) => Value::Float(*f),
+        Term::Bool(b) => Value::Bool(*b),
+        Term::Fun(f, x, m) => Value::Clo(Rc::new(Closure {
+            env: HashMap::default(),
+            func: f.clone(),
+            param: x.clone(),
+            body: m,
+        })),
+        _ => panic!("to_value({:?})", m),
+    }
+}
+
+fn of_value(v: Value) -> Result<Box<Term>, Error> {
+    match v {
+        Value::Int(i) => Ok(int(i)),
+        Value::Float(f) => Ok(float(f)),
+        Value::Bool(b) => Ok(bool(b)),
+        _ => unsupported("references"),
+    }
+}
+
+// terms have no store, handlers or continuations to step with
+fn unsupported<T>(what: &str) -> Result<T, Error> {
+    Err(Error::Unsupported(format!(
+        "{} in the small step semantics",
+        what
+    )))
+}
+
+fn raised(m: &Term) -> bool {
+    matches!(m, Term::Raise(v, _) if is_value(v))
+}
+
+// reduces the redex inside m, a raised value replaces the whole term
+fn congr(m: &Term, k: impl FnOnce(Box<Term>) -> Term) -> Result<Option<Box<Term>>, Error> {
+    if raised(m) {
+        return Ok(Some(Box::new(m.clone())));
+    }
+    Ok(step(m)?.map(|m| Box::new(k(m))))
+}
+
+// one call-by-value, left-to-right reduction step, None for values and
+// uncaught raises
+pub fn step(m0: &Term) -> Result<Option<Box<Term>>, Error> {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Fun(..) => Ok(None),
+        Var(x) => panic!("cannot find({:?})", x),
+        Op1(op1, m, pos) if !is_value(m) => congr(m, |m| Op1(*op1, m, *pos)),
+        Op1(op1, m, pos) => Ok(Some(of_value(eval_op1(op1, &to_value(m), *pos)?)?)),
+        Op2(op2, m, n, pos) if !is_value(m) => congr(m, |m| Op2(*op2, m, n.clone(), *pos)),
+        Op2(op2, m, n, pos) => match short_circuit(op2, &to_value(m)) {
+            Some(v) => Ok(Some(of_value(v)?)),
+            None if !is_value(n) => congr(n, |n| Op2(*op2, m.clone(), n, *pos)),
+            None => {
+                let v = eval_op2(op2, &to_value(m), &to_value(n), *pos)?;
+                Ok(Some(of_value(v)?))
+            }
+        },
+        App(m, n, pos) if !is_value(m) => congr(m, |m| App(m, n.clone(), *pos)),
+        App(m, n, pos) if !is_value(n) => congr(n, |n| App(m.clone(), n, *pos)),
+        App(m, n, _) => match m.as_ref() {
+            Fun(f, x, body) => Ok(Some(substitute(&substitute(body, f, m), x, n))),
+            _ => panic!("eval_App({:?})", m),
+        },
+        LetIn(x, m, n) if !is_value(m) => congr(m, |m| LetIn(x.clone(), m, n.clone())),
+        LetIn(x, m, n) => Ok(Some(substitute(n, x, m))),
+        Ifte(m, n1, n2) if !is_value(m) => congr(m, |m| Ifte(m, n1.clone(), n2.clone())),
+        Ifte(m, n1, n2) => match m.as_ref() {
+            Bool(true) => Ok(Some(n1.clone())),
+            Bool(false) => Ok(Some(n2.clone())),
+            _ => panic!("eval_Ifte({:?})", m0),
+        },
+        Raise(m, _) if is_value(m) => Ok(None),
+        Raise(m, pos) => congr(m, |m| Raise(m, *pos)),
+        Try(m, _, _) if is_value(m) => Ok(Some(m.clone())),
+        Try(m, x, n) => match m.as_ref() {
+            Raise(v, _) if is_value(v) => Ok(Some(substitute(n, x, v))),
+            _ => match step(m) {
+                Ok(m) => Ok(m.map(|m| Box::new(Try(m, x.clone(), n.clone())))),
+                Err(e @ Error::Unsupported(_)) => Err(e),
+                Err(e) => Ok(Some(substitute(n, x, &Int(e.code())))),
+            },
+        },
+        Effect(_, m) => Ok(Some(m.clone())),
+        Perform(op, m, pos) if !is_value(m) => congr(m, |m| Perform(op.clone(), m, *pos)),
+        Perform(op, _, pos) => Err(Error::Unhandled(op.to_string(), *pos)),
+        Handle(..) => unsupported("effect handlers"),
+        CallCC(..) => unsupported("callcc"),
+    }
+}
+
+pub fn reduce(m0: &Term) -> Result<Box<Term>, Exn<Box<Term>>> {
+    let mut m = Box::new(m0.clone());
+    loop {
+        match step(&m)? {
+            Some(m1) => m = m1,
+            None => match *m {
+                Term::Raise(v, pos) => return Err(Exn::Raise(v, pos)),
+                _ => return Ok(m),
+            },
+        }
+    }
+}
@@ -1,47 +1,58 @@
 use crate::{ast0, ast1, names::Name};
 use ahash::HashMap;
 
-pub type Ctx = HashMap<String, Name>;
+// effect operations are only named by perform and handle, so they live
+// apart from variables and neither can be read as the other
+#[derive(Clone, Default)]
+pub struct Ctx {
+    pub vars: HashMap<String, Name>,
+    pub ops: HashMap<String, Name>,
+}
 
 pub fn trans(ctx: &mut Ctx, m: &ast0::Term) -> Box<ast1::Term> {
     use ast0::Term::*;
     match m {
         Int(i) => ast1::int(*i),
+        Float(f) => ast1::float(*f),
         Bool(b) => ast1::bool(*b),
         Var(s) => {
-            let x = ctx.get(s).unwrap();
+            let x = ctx.vars.get(s).unwrap();
             ast1::var(x.clone())
         }
-        Op1(op1, m) => {
+        Op1(op1, m, pos) => {
             let op1 = trans_op1(op1);
             let m = trans(ctx, m);
-            ast1::op1(op1, m)
+            ast1::op1(op1, m, *pos)
         }
-        Op2(op2, m, n) => {
+        Op2(op2, m, n, pos) => {
             let op2 = trans_op2(op2);
             let m = trans(ctx, m);
             let n = trans(ctx, n);
-            ast1::op2(op2, m, n)
+            ast1::op2(op2, m, n, *pos)
         }
         Fun(f0, x0, m) => {
             let mut local = ctx.clone();
             let f = Name::new(f0.clone());
             let x = Name::new(x0.clone());
-            local.insert(f0.clone(), f.clone());
-            local.insert(x0.clone(), x.clone());
+            local.vars.insert(f0.clone(), f.clone());
+            local.vars.insert(x0.clone(), x.clone());
             let m = trans(&mut local, m);
             ast1::fun(f, x, m)
         }
-        App(m, n) => {
+        App(m, n, pos) => {
             let m = trans(ctx, m);
             let n = trans(ctx, n);
-            ast1::app(m, n)
+            ast1::app(m, n, *pos)
         }
         LetIn(x0, m, n) => {
             let m = trans(ctx, m);
             let mut local = ctx.clone();
-            let x = Name::new(x0.clone());
-            local.insert(x0.clone(), x.clone());
+            // let rec f x := m in n binds the same name as the function
+            let x = match m.as_ref() {
+                ast1::Term::Fun(f, ..) if f.to_string() == *x0 => f.clone(),
+                _ => Name::new(x0.clone()),
+            };
+            local.vars.insert(x0.clone(), x.clone());
             let n = trans(&mut local, n);
             ast1::letin(x, m, n)
         }
@@ -51,6 +62,55 @@ pub fn trans(ctx: &mut Ctx, m: &ast0::Term) -> Box<ast1::Term> {
             let n2 = trans(ctx, n2);
             ast1::ifte(m, n1, n2)
         }
+        Raise(m, pos) => {
+            let m = trans(ctx, m);
+            ast1::raise(m, *pos)
+        }
+        Try(m, x0, n) => {
+            let m = trans(ctx, m);
+            let mut local = ctx.clone();
+            let x = Name::new(x0.clone());
+            local.vars.insert(x0.clone(), x.clone());
+            let n = trans(&mut local, n);
+            ast1::trywith(m, x, n)
+        }
+        Effect(op0, m) => {
+            let mut local = ctx.clone();
+            let op = Name::new(op0.clone());
+            local.ops.insert(op0.clone(), op.clone());
+            let m = trans(&mut local, m);
+            ast1::effect(op, m)
+        }
+        Perform(op0, m, pos) => {
+            let op = ctx.ops.get(op0).unwrap().clone();
+            let m = trans(ctx, m);
+            ast1::perform(op, m, *pos)
+        }
+        Handle(m, x0, n, cases) => {
+            let m = trans(ctx, m);
+            let mut local = ctx.clone();
+            let x = Name::new(x0.clone());
+            local.vars.insert(x0.clone(), x.clone());
+            let n = trans(&mut local, n);
+            let cases = cases
+                .iter()
+                .map(|(op0, y0, k0, n)| {
+                    let op = ctx.ops.get(op0).unwrap().clone();
+                    let mut local = ctx.clone();
+                    let y = Name::new(y0.clone());
+                    let k = Name::new(k0.clone());
+                    local.vars.insert(y0.clone(), y.clone());
+                    local.vars.insert(k0.clone(), k.clone());
+                    let n = trans(&mut local, n);
+                    (op, y, k, n)
+                })
+                .collect();
+            ast1::handle(m, x, n, cases)
+        }
+        CallCC(m, pos) => {
+            let m = trans(ctx, m);
+            ast1::callcc(m, *pos)
+        }
     }
 }
 
@@ -59,6 +119,10 @@ fn trans_op1(op1: &ast0::Op1) -> ast1::Op1 {
     match op1 {
         Neg => ast1::Op1::Neg,
         Not => ast1::Op1::Not,
+        IntOfFloat => ast1::Op1::IntOfFloat,
+        FloatOfInt => ast1::Op1::FloatOfInt,
+        MkRef => ast1::Op1::MkRef,
+        Deref => ast1::Op1::Deref,
     }
 }
 
@@ -69,6 +133,7 @@ fn trans_op2(op2: &ast0::Op2) -> ast1::Op2 {
         Sub => ast1::Op2::Sub,
         Mul => ast1::Op2::Mul,
         Div => ast1::Op2::Div,
+        Mod => ast1::Op2::Mod,
         Lte => ast1::Op2::Lte,
         Gte => ast1::Op2::Gte,
         Lt => ast1::Op2::Lt,
@@ -77,5 +142,6 @@ fn trans_op2(op2: &ast0::Op2) -> ast1::Op2 {
         Neq => ast1::Op2::Neq,
         And => ast1::Op2::And,
         Or => ast1::Op2::Or,
+        Assign => ast1::Op2::Assign,
     }
 }
@@ -1,80 +1,131 @@
-use crate::{ast0, ast2};
+use crate::{ast0, ast1, ast2, error::Error};
 use ahash::HashMap;
 use std::rc::*;
 
 pub type Ctx<'a> = Rc<HashMap<String, ast2::Term<'a>>>;
 
-pub fn trans<'a>(ctx: Ctx<'a>, m: &'a ast0::Term) -> ast2::Term<'a> {
+pub fn trans<'a>(ctx: Ctx<'a>, m: &'a ast0::Term) -> Result<ast2::Term<'a>, Error> {
+    if let Some(what) = unsupported(m) {
+        return Err(Error::Unsupported(format!(
+            "{} in the higher-order translation",
+            what
+        )));
+    }
+    Ok(trans_term(ctx, m))
+}
+
+// the bodies of binders are translated when they are applied, so the term
+// is checked up front for what the translation cannot express
+fn unsupported(m0: &ast0::Term) -> Option<&'static str> {
+    use ast0::Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => None,
+        Effect(..) | Perform(..) | Handle(..) => Some("effect handlers"),
+        CallCC(..) => Some("callcc"),
+        Op1(_, m, _) | Fun(_, _, m) | Raise(m, _) => unsupported(m),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            unsupported(m).or_else(|| unsupported(n))
+        }
+        Ifte(m, n1, n2) => unsupported(m)
+            .or_else(|| unsupported(n1))
+            .or_else(|| unsupported(n2)),
+    }
+}
+
+fn trans_term<'a>(ctx: Ctx<'a>, m: &'a ast0::Term) -> ast2::Term<'a> {
     use ast0::Term::*;
     match m {
         Int(i) => ast2::int(*i),
+        Float(f) => ast2::float(*f),
         Bool(b) => ast2::bool(*b),
         Var(s) => ctx.get(s).unwrap().clone(),
-        Op1(op1, m) => {
+        Op1(op1, m, pos) => {
             let op1 = trans_op1(op1);
-            let m = trans(ctx, &*m);
-            ast2::op1(op1, m)
+            let m = trans_term(ctx, m);
+            ast2::op1(op1, m, *pos)
         }
-        Op2(op2, m, n) => {
+        Op2(op2, m, n, pos) => {
             let op2 = trans_op2(op2);
-            let m = trans(ctx.clone(), &*m);
-            let n = trans(ctx.clone(), &*n);
-            ast2::op2(op2, m, n)
+            let m = trans_term(ctx.clone(), m);
+            let n = trans_term(ctx.clone(), n);
+            ast2::op2(op2, m, n, *pos)
         }
         Fun(f0, x0, m0) => {
             let bnd = Rc::new(move |fv, xv| {
                 let mut ctx = (*ctx).clone();
                 ctx.insert(f0.clone(), fv);
                 ctx.insert(x0.clone(), xv);
-                trans(Rc::new(ctx), &*m0)
+                trans_term(Rc::new(ctx), &*m0)
             });
             ast2::fun(bnd)
         }
-        App(m, n) => {
-            let m = trans(ctx.clone(), &*m);
-            let n = trans(ctx.clone(), &*n);
+        App(m, n, _) => {
+            let m = trans_term(ctx.clone(), m);
+            let n = trans_term(ctx.clone(), n);
             ast2::app(m, n)
         }
         LetIn(x0, m, n) => {
-            let m = trans(ctx.clone(), &*m);
+            let m = trans_term(ctx.clone(), m);
             let bnd = Rc::new(move |xv| {
                 let mut ctx = (*ctx).clone();
                 ctx.insert(x0.clone(), xv);
-                trans(Rc::new(ctx), &*n)
+                trans_term(Rc::new(ctx), &*n)
             });
             ast2::letin(m, bnd)
         }
         Ifte(m, n1, n2) => {
-            let m = trans(ctx.clone(), &*m);
-            let n1 = trans(ctx.clone(), &*n1);
-            let n2 = trans(ctx.clone(), &*n2);
+            let m = trans_term(ctx.clone(), m);
+            let n1 = trans_term(ctx.clone(), n1);
+            let n2 = trans_term(ctx.clone(), n2);
             ast2::ifte(m, n1, n2)
         }
+        Raise(m, pos) => {
+            let m = trans_term(ctx, m);
+            ast2::raise(m, *pos)
+        }
+        Try(m, x0, n) => {
+            let m = trans_term(ctx.clone(), m);
+            let bnd = Rc::new(move |xv| {
+                let mut ctx = (*ctx).clone();
+                ctx.insert(x0.clone(), xv);
+                trans_term(Rc::new(ctx), n)
+            });
+            ast2::trywith(m, bnd)
+        }
+        Effect(..) | Perform(..) | Handle(..) | CallCC(..) => {
+            unreachable!("trans_term({:?})", m)
+        }
     }
 }
 
-fn trans_op1(op1: &ast0::Op1) -> ast2::Op1 {
+fn trans_op1(op1: &ast0::Op1) -> ast1::Op1 {
     use ast0::Op1::*;
     match op1 {
-        Neg => ast2::Op1::Neg,
-        Not => ast2::Op1::Not,
+        Neg => ast1::Op1::Neg,
+        Not => ast1::Op1::Not,
+        IntOfFloat => ast1::Op1::IntOfFloat,
+        FloatOfInt => ast1::Op1::FloatOfInt,
+        MkRef => ast1::Op1::MkRef,
+        Deref => ast1::Op1::Deref,
     }
 }
 
-fn trans_op2(op2: &ast0::Op2) -> ast2::Op2 {
+fn trans_op2(op2: &ast0::Op2) -> ast1::Op2 {
     use ast0::Op2::*;
     match op2 {
-        Add => ast2::Op2::Add,
-        Sub => ast2::Op2::Sub,
-        Mul => ast2::Op2::Mul,
-        Div => ast2::Op2::Div,
-        Lte => ast2::Op2::Lte,
-        Gte => ast2::Op2::Gte,
-        Lt => ast2::Op2::Lt,
-        Gt => ast2::Op2::Gt,
-        Eq => ast2::Op2::Eq,
-        Neq => ast2::Op2::Neq,
-        And => ast2::Op2::And,
-        Or => ast2::Op2::Or,
+        Add => ast1::Op2::Add,
+        Sub => ast1::Op2::Sub,
+        Mul => ast1::Op2::Mul,
+        Div => ast1::Op2::Div,
+        Mod => ast1::Op2::Mod,
+        Lte => ast1::Op2::Lte,
+        Gte => ast1::Op2::Gte,
+        Lt => ast1::Op2::Lt,
+        Gt => ast1::Op2::Gt,
+        Eq => ast1::Op2::Eq,
+        Neq => ast1::Op2::Neq,
+        And => ast1::Op2::And,
+        Or => ast1::Op2::Or,
+        Assign => ast1::Op2::Assign,
     }
 }
@@ -0,0 +1,143 @@
+use crate::{
+    ast1::{self, free_vars},
+    ast3,
+    error::Error,
+    names::Name,
+};
+use ahash::{HashMap, HashSet};
+
+// closure conversion, every function is lifted to the top level and
+// reads the variables it captures from the closure it is called through,
+// effects and callcc need the rest of the program and are not converted
+pub fn trans(m: &ast1::Term) -> Result<ast3::Prog, Error> {
+    let mut lift = Lift {
+        funs: vec![],
+        labels: HashSet::default(),
+    };
+    let main = lift.conv(&HashMap::default(), m)?;
+    Ok(ast3::Prog {
+        funs: lift.funs,
+        main,
+    })
+}
+
+struct Lift {
+    funs: Vec<ast3::Def>,
+    labels: HashSet<String>,
+}
+
+// how the captured variables of the function being converted are reached
+type Fields = HashMap<Name, ast3::Term>;
+
+impl Lift {
+    // functions are labelled by their name, anonymous ones by lambda,
+    // followed by a number where that is already taken
+    fn label(&mut self, f: &Name) -> Name {
+        let name = match f.to_string() {
+            s if s.is_empty() => String::from("lambda"),
+            s => s,
+        };
+        let mut label = name.clone();
+        for i in 1.. {
+            if self.labels.insert(label.clone()) {
+                break;
+            }
+            label = format!("{}{}", name, i);
+        }
+        Name::new(label)
+    }
+
+    fn access(&self, fields: &Fields, x: &Name) -> Box<ast3::Term> {
+        match fields.get(x) {
+            Some(m) => Box::new(m.clone()),
+            None => ast3::var(x.clone()),
+        }
+    }
+
+    // m0 is a function, labelled after f
+    fn lift(
+        &mut self,
+        fields: &Fields,
+        f: &Name,
+        m0: &ast1::Term,
+    ) -> Result<Box<ast3::Term>, Error> {
+        let ast1::Term::Fun(g, x, m) = m0 else {
+            panic!("lift({:?})", m0)
+        };
+        let mut fvs: Vec<Name> = free_vars(m0).into_iter().collect();
+        fvs.sort();
+        // a named function reaches itself through the closure it is called
+        // with, so the closure parameter takes its name
+        let clo = match g.to_string().is_empty() {
+            true => Name::new(String::from("clo")),
+            false => g.clone(),
+        };
+        let inner = fvs
+            .iter()
+            .enumerate()
+            .map(|(i, y)| (y.clone(), ast3::Term::Field(clo.clone(), i)))
+            .collect();
+        let name = self.label(f);
+        let body = self.conv(&inner, m)?;
+        self.funs.push(ast3::Def {
+            name,
+            clo,
+            param: x.clone(),
+            body,
+        });
+        let env = fvs.iter().map(|y| *self.access(fields, y)).collect();
+        Ok(ast3::clo(self.funs.len() - 1, env))
+    }
+
+    fn conv(&mut self, fields: &Fields, m0: &ast1::Term) -> Result<Box<ast3::Term>, Error> {
+        use ast1::Term::*;
+        Ok(match m0 {
+            Int(i) => ast3::int(*i),
+            Float(f) => ast3::float(*f),
+            Bool(b) => ast3::bool(*b),
+            Var(x) => self.access(fields, x),
+            Op1(op, m, pos) => ast3::op1(*op, self.conv(fields, m)?, *pos),
+            Op2(op, m, n, pos) => {
+                let m = self.conv(fields, m)?;
+                let n = self.conv(fields, n)?;
+                ast3::op2(*op, m, n, *pos)
+            }
+            Fun(f, ..) => self.lift(fields, f, m0)?,
+            App(m, n, pos) => {
+                let m = self.conv(fields, m)?;
+                let n = self.conv(fields, n)?;
+                ast3::app(m, n, *pos)
+            }
+            LetIn(x, m, n) => {
+                let m = match m.as_ref() {
+                    Fun(f, ..) if f.to_string().is_empty() => self.lift(fields, x, m)?,
+                    _ => self.conv(fields, m)?,
+                };
+                let n = self.conv(fields, n)?;
+                ast3::letin(x.clone(), m, n)
+            }
+            Ifte(m, n1, n2) => {
+                let m = self.conv(fields, m)?;
+                let n1 = self.conv(fields, n1)?;
+                let n2 = self.conv(fields, n2)?;
+                ast3::ifte(m, n1, n2)
+            }
+            Raise(m, pos) => ast3::raise(self.conv(fields, m)?, *pos),
+            Try(m, x, n) => {
+                let m = self.conv(fields, m)?;
+                let n = self.conv(fields, n)?;
+                ast3::trywith(m, x.clone(), n)
+            }
+            Effect(..) | Perform(..) | Handle(..) => {
+                return Err(Error::Unsupported(String::from(
+                    "effect handlers in closure conversion",
+                )));
+            }
+            CallCC(..) => {
+                return Err(Error::Unsupported(String::from(
+                    "callcc in closure conversion",
+                )));
+            }
+        })
+    }
+}
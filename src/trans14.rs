@@ -0,0 +1,146 @@
+use crate::{
+    ast1,
+    ast4::{Atom, Expr, Term},
+    names::Name,
+};
+
+// conversion to a-normal form, lets nested in a binding are flattened
+// into the enclosing sequence
+pub fn trans(m: &ast1::Term) -> Term {
+    Anf { next: 0 }.term(m)
+}
+
+struct Anf {
+    next: usize,
+}
+
+type Binds = Vec<(Name, Expr)>;
+
+impl Anf {
+    fn fresh(&mut self) -> Name {
+        self.next += 1;
+        Name::new(format!("_{}", self.next))
+    }
+
+    fn term(&mut self, m: &ast1::Term) -> Term {
+        let mut binds = vec![];
+        let e = self.expr(m, &mut binds);
+        binds
+            .into_iter()
+            .rev()
+            .fold(Term::Tail(e), |m, (x, e)| Term::Let(x, e, Box::new(m)))
+    }
+
+    fn atom(&mut self, m: &ast1::Term, binds: &mut Binds) -> Atom {
+        match self.expr(m, binds) {
+            Expr::Atom(a) => a,
+            e => {
+                let x = self.fresh();
+                binds.push((x.clone(), e));
+                Atom::Var(x)
+            }
+        }
+    }
+
+    // the bindings m needs are pushed onto binds, what is left is returned
+    fn expr(&mut self, m0: &ast1::Term, binds: &mut Binds) -> Expr {
+        use ast1::{Op2::*, Term::*};
+        match m0 {
+            Int(i) => Expr::Atom(Atom::Int(*i)),
+            Float(f) => Expr::Atom(Atom::Float(*f)),
+            Bool(b) => Expr::Atom(Atom::Bool(*b)),
+            Var(x) => Expr::Atom(Atom::Var(x.clone())),
+            Op1(op, m, pos) => Expr::Op1(*op, self.atom(m, binds), *pos),
+            // the right operand of a short circuit operator is only evaluated
+            // on demand, a non boolean one is returned as is
+            Op2(And, m, n, _) => {
+                let a = self.atom(m, binds);
+                let tail = Term::Tail(Expr::Atom(Atom::Bool(false)));
+                Expr::Ifte(a, Box::new(self.term(n)), Box::new(tail))
+            }
+            Op2(Or, m, n, _) => {
+                let a = self.atom(m, binds);
+                let tail = Term::Tail(Expr::Atom(Atom::Bool(true)));
+                Expr::Ifte(a, Box::new(tail), Box::new(self.term(n)))
+            }
+            Op2(op, m, n, pos) => {
+                let a = self.atom(m, binds);
+                let b = self.atom(n, binds);
+                Expr::Op2(*op, a, b, *pos)
+            }
+            Fun(f, x, m) => Expr::Fun(f.clone(), x.clone(), Box::new(self.term(m))),
+            App(m, n, pos) => {
+                let a = self.atom(m, binds);
+                let b = self.atom(n, binds);
+                Expr::App(a, b, *pos)
+            }
+            LetIn(x, m, n) => {
+                let e = self.expr(m, binds);
+                binds.push((x.clone(), e));
+                self.expr(n, binds)
+            }
+            Ifte(m, n1, n2) => {
+                let a = self.atom(m, binds);
+                Expr::Ifte(a, Box::new(self.term(n1)), Box::new(self.term(n2)))
+            }
+            Raise(m, pos) => Expr::Raise(self.atom(m, binds), *pos),
+            Try(m, x, n) => Expr::Try(Box::new(self.term(m)), x.clone(), Box::new(self.term(n))),
+            Effect(op, m) => Expr::Effect(op.clone(), Box::new(self.term(m))),
+            Perform(op, m, pos) => Expr::Perform(op.clone(), self.atom(m, binds), *pos),
+            Handle(m, x, n, cases) => {
+                let cases = cases
+                    .iter()
+                    .map(|(op, y, k, n)| (op.clone(), y.clone(), k.clone(), Box::new(self.term(n))))
+                    .collect();
+                Expr::Handle(
+                    Box::new(self.term(m)),
+                    x.clone(),
+                    Box::new(self.term(n)),
+                    cases,
+                )
+            }
+            CallCC(m, pos) => Expr::CallCC(self.atom(m, binds), *pos),
+        }
+    }
+}
+
+// back to ast1, so that every backend can run a converted program
+pub fn untrans(m0: &Term) -> Box<ast1::Term> {
+    match m0 {
+        Term::Let(x, e, m) => ast1::letin(x.clone(), untrans_expr(e), untrans(m)),
+        Term::Tail(e) => untrans_expr(e),
+    }
+}
+
+fn untrans_atom(a: &Atom) -> Box<ast1::Term> {
+    match a {
+        Atom::Int(i) => ast1::int(*i),
+        Atom::Float(f) => ast1::float(*f),
+        Atom::Bool(b) => ast1::bool(*b),
+        Atom::Var(x) => ast1::var(x.clone()),
+    }
+}
+
+fn untrans_expr(e: &Expr) -> Box<ast1::Term> {
+    use Expr::*;
+    match e {
+        Atom(a) => untrans_atom(a),
+        Op1(op, a, pos) => ast1::op1(*op, untrans_atom(a), *pos),
+        Op2(op, a, b, pos) => ast1::op2(*op, untrans_atom(a), untrans_atom(b), *pos),
+        Fun(f, x, m) => ast1::fun(f.clone(), x.clone(), untrans(m)),
+        App(a, b, pos) => ast1::app(untrans_atom(a), untrans_atom(b), *pos),
+        Ifte(a, m1, m2) => ast1::ifte(untrans_atom(a), untrans(m1), untrans(m2)),
+        Raise(a, pos) => ast1::raise(untrans_atom(a), *pos),
+        Try(m, x, n) => ast1::trywith(untrans(m), x.clone(), untrans(n)),
+        Effect(op, m) => ast1::effect(op.clone(), untrans(m)),
+        Perform(op, a, pos) => ast1::perform(op.clone(), untrans_atom(a), *pos),
+        Handle(m, x, n, cases) => {
+            let cases = cases
+                .iter()
+                .map(|(op, y, k, n)| (op.clone(), y.clone(), k.clone(), untrans(n)))
+                .collect();
+            ast1::handle(untrans(m), x.clone(), untrans(n), cases)
+        }
+        CallCC(a, pos) => ast1::callcc(untrans_atom(a), *pos),
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    ast1,
+    ast5::{Atom, Cont, Term},
+    error::Error,
+    names::Name,
+};
+
+// one pass cps conversion, the continuation of a subterm is kept as a
+// function of the compiler where possible so that no administrative
+// redexes are built
+pub fn trans(m: &ast1::Term) -> Result<Term, Error> {
+    if let Some(what) = unsupported(m) {
+        return Err(Error::Unsupported(format!("{} in cps conversion", what)));
+    }
+    Ok(Cps { next: 0 }.conv(m, K::Obj(Cont::Halt)))
+}
+
+// effects and callcc capture the continuation, which would have to become
+// a value of the program, the conversion is checked for them up front as
+// its continuations cannot fail
+fn unsupported(m0: &ast1::Term) -> Option<&'static str> {
+    use ast1::Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => None,
+        Effect(..) | Perform(..) | Handle(..) => Some("effect handlers"),
+        CallCC(..) => Some("callcc"),
+        Op1(_, m, _) | Fun(_, _, m) | Raise(m, _) => unsupported(m),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            unsupported(m).or_else(|| unsupported(n))
+        }
+        Ifte(m, n1, n2) => unsupported(m)
+            .or_else(|| unsupported(n1))
+            .or_else(|| unsupported(n2)),
+    }
+}
+
+struct Cps {
+    next: usize,
+}
+
+type Meta<'a> = Box<dyn FnOnce(&mut Cps, Atom) -> Term + 'a>;
+
+enum K<'a> {
+    Obj(Cont),
+    Meta(Meta<'a>),
+    // let x := [] in the term built by the function
+    Let(Name, Box<dyn FnOnce(&mut Cps) -> Term + 'a>),
+}
+
+// an atom that can be evaluated later than its operands without any
+// effect or error moving across m
+fn simple(m0: &ast1::Term) -> bool {
+    use ast1::Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) | Fun(..) => true,
+        Op1(_, m, _) => simple(m),
+        Op2(ast1::Op2::And | ast1::Op2::Or, ..) => false,
+        Op2(_, m, n, _) => simple(m) && simple(n),
+        _ => false,
+    }
+}
+
+impl Cps {
+    fn fresh(&mut self) -> Name {
+        self.next += 1;
+        Name::new(format!("_{}", self.next))
+    }
+
+    fn apply(&mut self, k: K, a: Atom) -> Term {
+        match k {
+            K::Obj(c) => Term::Ret(c, a),
+            K::Meta(f) => f(self, a),
+            K::Let(x, f) => Term::Let(x, a, Box::new(f(self))),
+        }
+    }
+
+    fn reify(&mut self, k: K) -> Cont {
+        match k {
+            K::Obj(c) => c,
+            K::Meta(f) => {
+                let x = self.fresh();
+                Cont::Fun(x.clone(), Box::new(f(self, Atom::Var(x))))
+            }
+            K::Let(x, f) => Cont::Fun(x, Box::new(f(self))),
+        }
+    }
+
+    // k is named first when m jumps to it from more than one place
+    fn join(&mut self, k: K, m: impl FnOnce(&mut Self, Cont) -> Term) -> Term {
+        match self.reify(k) {
+            Cont::Fun(x, n) => {
+                let j = self.fresh();
+                let m = m(self, Cont::Var(j.clone()));
+                Term::LetCont(j, x, n, Box::new(m))
+            }
+            c => m(self, c),
+        }
+    }
+
+    // a is computed before next when next is not simple
+    fn hold<'a>(&mut self, a: Atom, next: &ast1::Term, m: Meta<'a>) -> Term {
+        match a {
+            Atom::Op1(..) | Atom::Op2(..) if !simple(next) => {
+                let x = self.fresh();
+                let m = m(self, Atom::Var(x.clone()));
+                Term::Let(x, a, Box::new(m))
+            }
+            a => m(self, a),
+        }
+    }
+
+    fn conv<'a>(&mut self, m0: &'a ast1::Term, k: K<'a>) -> Term {
+        use ast1::{Op2::*, Term::*};
+        match m0 {
+            Int(i) => self.apply(k, Atom::Int(*i)),
+            Float(f) => self.apply(k, Atom::Float(*f)),
+            Bool(b) => self.apply(k, Atom::Bool(*b)),
+            Var(x) => self.apply(k, Atom::Var(x.clone())),
+            Op1(op, m, pos) => self.conv(
+                m,
+                K::Meta(Box::new(move |s, a| {
+                    s.apply(k, Atom::Op1(*op, Box::new(a), *pos))
+                })),
+            ),
+            Op2(op @ (And | Or), m, n, _) => self.conv(
+                m,
+                K::Meta(Box::new(move |s, a| {
+                    s.join(k, move |s, c| {
+                        let n = s.conv(n, K::Obj(c.clone()));
+                        let other = Term::Ret(c, Atom::Bool(*op == Or));
+                        match op {
+                            And => Term::Ifte(a, Box::new(n), Box::new(other)),
+                            _ => Term::Ifte(a, Box::new(other), Box::new(n)),
+                        }
+                    })
+                })),
+            ),
+            Op2(op, m, n, pos) => self.conv(
+                m,
+                K::Meta(Box::new(move |s, a| {
+                    s.hold(
+                        a,
+                        n,
+                        Box::new(move |s, a| {
+                            s.conv(
+                                n,
+                                K::Meta(Box::new(move |s, b| {
+                                    s.apply(k, Atom::Op2(*op, Box::new(a), Box::new(b), *pos))
+                                })),
+                            )
+                        }),
+                    )
+                })),
+            ),
+            Fun(f, x, m) => {
+                let c = Name::new(String::from("_k"));
+                let m = self.conv(m, K::Obj(Cont::Var(c.clone())));
+                self.apply(k, Atom::Fun(f.clone(), x.clone(), c, Box::new(m)))
+            }
+            App(m, n, pos) => self.conv(
+                m,
+                K::Meta(Box::new(move |s, a| {
+                    s.hold(
+                        a,
+                        n,
+                        Box::new(move |s, a| {
+                            s.conv(
+                                n,
+                                K::Meta(Box::new(move |s, b| {
+                                    let c = s.reify(k);
+                                    Term::App(a, b, c, *pos)
+                                })),
+                            )
+                        }),
+                    )
+                })),
+            ),
+            LetIn(x, m, n) => self.conv(m, K::Let(x.clone(), Box::new(move |s| s.conv(n, k)))),
+            Ifte(m, n1, n2) => self.conv(
+                m,
+                K::Meta(Box::new(move |s, a| {
+                    s.join(k, move |s, c| {
+                        let n1 = s.conv(n1, K::Obj(c.clone()));
+                        let n2 = s.conv(n2, K::Obj(c));
+                        Term::Ifte(a, Box::new(n1), Box::new(n2))
+                    })
+                })),
+            ),
+            Raise(m, pos) => self.conv(m, K::Meta(Box::new(move |_, a| Term::Raise(a, *pos)))),
+            Try(m, x, n) => self.join(k, move |s, c| {
+                let n = s.conv(n, K::Obj(c.clone()));
+                let m = s.conv(m, K::Meta(Box::new(move |_, a| Term::Uncatch(c, a))));
+                Term::Catch(x.clone(), Box::new(n), Box::new(m))
+            }),
+            Effect(..) | Perform(..) | Handle(..) | CallCC(..) => {
+                unreachable!("conv({:?}), rejected by trans", m0)
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    ast1::{self, free_vars},
+    ast6,
+    error::Error,
+    names::Name,
+};
+use ahash::HashSet;
+
+// defunctionalization of a closed program, a fun becomes its constructor
+// applied to its free variables and its body a case of apply, effects and
+// callcc have no first order counterpart here
+pub fn trans(m: &ast1::Term) -> Result<ast6::Prog, Error> {
+    let mut defun = Defun {
+        cases: vec![],
+        cons: HashSet::default(),
+    };
+    let main = defun.conv(m)?;
+    Ok(ast6::Prog {
+        cases: defun.cases,
+        main,
+    })
+}
+
+struct Defun {
+    cases: Vec<ast6::Case>,
+    cons: HashSet<String>,
+}
+
+impl Defun {
+    // the name of the function capitalized, anonymous ones are Lambda,
+    // followed by a number where that is already taken
+    fn con(&mut self, f: &Name) -> Name {
+        let mut chars = f.to_string().chars().collect::<Vec<_>>().into_iter();
+        let name = match chars.next() {
+            Some(c) => c.to_ascii_uppercase().to_string() + &chars.collect::<String>(),
+            None => String::from("Lambda"),
+        };
+        let mut con = name.clone();
+        for i in 1.. {
+            if self.cons.insert(con.clone()) {
+                break;
+            }
+            con = format!("{}{}", name, i);
+        }
+        Name::new(con)
+    }
+
+    // m0 is a function, its constructor is named after f
+    fn defun(&mut self, f: &Name, m0: &ast1::Term) -> Result<Box<ast6::Term>, Error> {
+        let ast1::Term::Fun(g, x, m) = m0 else {
+            panic!("defun({:?})", m0)
+        };
+        let mut fields: Vec<Name> = free_vars(m0).into_iter().collect();
+        fields.sort();
+        // the case binds the constructor it matched to the function name, a
+        // recursive call applies it again
+        let func = match g.to_string().is_empty() {
+            true => Name::new(String::from("clo")),
+            false => g.clone(),
+        };
+        let con = self.con(f);
+        let body = self.conv(m)?;
+        self.cases.push(ast6::Case {
+            con,
+            func,
+            fields: fields.clone(),
+            param: x.clone(),
+            body,
+        });
+        Ok(Box::new(ast6::Term::Con(self.cases.len() - 1, fields)))
+    }
+
+    fn conv(&mut self, m0: &ast1::Term) -> Result<Box<ast6::Term>, Error> {
+        use ast1::Term::*;
+        use ast6::Term as T;
+        let m = match m0 {
+            Int(i) => T::Int(*i),
+            Float(f) => T::Float(*f),
+            Bool(b) => T::Bool(*b),
+            Var(x) => T::Var(x.clone()),
+            Op1(op, m, pos) => T::Op1(*op, self.conv(m)?, *pos),
+            Op2(op, m, n, pos) => T::Op2(*op, self.conv(m)?, self.conv(n)?, *pos),
+            Fun(f, ..) => return self.defun(f, m0),
+            App(m, n, pos) => T::Apply(self.conv(m)?, self.conv(n)?, *pos),
+            LetIn(x, m, n) => {
+                let m = match m.as_ref() {
+                    Fun(f, ..) if f.to_string().is_empty() => self.defun(x, m)?,
+                    _ => self.conv(m)?,
+                };
+                T::LetIn(x.clone(), m, self.conv(n)?)
+            }
+            Ifte(m, n1, n2) => T::Ifte(self.conv(m)?, self.conv(n1)?, self.conv(n2)?),
+            Raise(m, pos) => T::Raise(self.conv(m)?, *pos),
+            Try(m, x, n) => T::Try(self.conv(m)?, x.clone(), self.conv(n)?),
+            Effect(..) | Perform(..) | Handle(..) => {
+                return Err(Error::Unsupported(String::from(
+                    "effect handlers in defunctionalization",
+                )));
+            }
+            CallCC(..) => {
+                return Err(Error::Unsupported(String::from(
+                    "callcc in defunctionalization",
+                )));
+            }
+        };
+        Ok(Box::new(m))
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+mod generator;
+
+use ahash::HashMap;
+use common::{resolve, show, source};
+use proptest::prelude::*;
+use rust_test::{
+    ast1::alpha_eq,
+    ast4::{Expr, Term},
+    eval::eval,
+    trans14,
+};
+use std::cell::RefCell;
+
+fn anf(src: &str) -> Term {
+    trans14::trans(&source(src))
+}
+
+fn run(src: &str) -> String {
+    let tm = trans14::untrans(&anf(src));
+    show(eval(&RefCell::new(HashMap::default()), &tm))
+}
+
+// the names bound by the outermost sequence of lets
+fn bound(mut m: &Term) -> Vec<String> {
+    let mut names = vec![];
+    while let Term::Let(x, _, n) = m {
+        names.push(x.to_string());
+        m = n;
+    }
+    names
+}
+
+#[test]
+fn intermediate_results_are_named() {
+    let m = anf("let f x := x in f (1 + 2) * f 3");
+    assert_eq!(
+        m.to_string(),
+        "let f := (fun x =>\n  x\n) in\nlet _1 := 1 + 2 in\nlet _2 := f _1 in\nlet _3 := f 3 in\n_2 * _3"
+    );
+}
+
+#[test]
+fn lets_are_flattened() {
+    let m = anf("let x := (let y := (let z := 1 in z + 1) in y * 2) in x - 1");
+    assert_eq!(bound(&m), ["z", "y", "x"]);
+    assert!(matches!(
+        m,
+        Term::Let(_, Expr::Atom(_), ref n) if matches!(**n, Term::Let(_, Expr::Op2(..), _))
+    ));
+    // nested lets in operands end up in the same sequence
+    let m = anf("(let a := 1 in a + 1) * (let b := 2 in b)");
+    assert_eq!(bound(&m), ["a", "_1", "b"]);
+    // but not out of a function body or a branch
+    let m = anf("let f x := (let y := x in y) in if true then (let z := 1 in z) else 0");
+    assert_eq!(bound(&m), ["f"]);
+}
+
+#[test]
+fn evaluation_order() {
+    let src = "let r := ref 0 in
+let tick u := r := !r * 10 + u in
+let f x y := x + y in
+let v := f (tick 1) (tick 2) + tick 3 in !r";
+    assert_eq!(run(src), "Int(123)");
+    let src = "let r := ref 0 in let u := (r := 1) == 0 || (r := 2) == 0 in !r";
+    assert_eq!(run(src), "Int(2)");
+    let src = "let r := ref 0 in let u := (r := 1) == 1 || (r := 2) == 0 in !r";
+    assert_eq!(run(src), "Int(1)");
+}
+
+#[test]
+fn short_circuit() {
+    let m = anf("let f x := x in f true && f false");
+    assert_eq!(bound(&m), ["f", "_1"]);
+    assert_eq!(run("false && 1 / 0 == 0"), "Bool(false)");
+    assert_eq!(run("true || 1 / 0 == 0"), "Bool(true)");
+    assert_eq!(
+        run("true && 1 / 0 == 0"),
+        "Error(DivByZero(Pos { line: 1, col: 11 }))"
+    );
+}
+
+#[test]
+fn fresh_names() {
+    let m = anf("let f x := x in f (f (f 1))");
+    assert_eq!(bound(&m), ["f", "_1", "_2"]);
+    assert_eq!(
+        m.to_string(),
+        "let f := (fun x =>\n  x\n) in\nlet _1 := f 1 in\nlet _2 := f _1 in\nf _2"
+    );
+}
+
+proptest! {
+    // a program in a-normal form converts to itself
+    #[test]
+    fn idempotent(m in generator::program(false)) {
+        let tm = resolve(&m);
+        let m1 = trans14::untrans(&trans14::trans(&tm));
+        let m2 = trans14::untrans(&trans14::trans(&m1));
+        prop_assert!(alpha_eq(&m1, &m2), "{}\n{}", m1, m2);
+    }
+}
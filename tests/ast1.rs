@@ -0,0 +1,168 @@
+use ahash::HashSet;
+use proptest::prelude::*;
+use rust_test::{ast1::*, error::Pos, names::Name};
+
+#[derive(Debug, Clone)]
+enum Shape {
+    Int(i8),
+    Var(u8),
+    Fun(u8, Box<Shape>),
+    RecFun(Box<Shape>),
+    App(Box<Shape>, Box<Shape>),
+    Add(Box<Shape>, Box<Shape>),
+    LetIn(u8, Box<Shape>, Box<Shape>),
+    Ifte(Box<Shape>, Box<Shape>, Box<Shape>),
+    Try(Box<Shape>, Box<Shape>),
+}
+
+fn shape() -> impl Strategy<Value = Shape> {
+    let leaf = prop_oneof![
+        any::<i8>().prop_map(Shape::Int),
+        any::<u8>().prop_map(Shape::Var)
+    ];
+    leaf.prop_recursive(6, 48, 3, |inner| {
+        prop_oneof![
+            (any::<u8>(), inner.clone()).prop_map(|(x, m)| Shape::Fun(x, Box::new(m))),
+            inner.clone().prop_map(|m| Shape::RecFun(Box::new(m))),
+            (inner.clone(), inner.clone()).prop_map(|(m, n)| Shape::App(Box::new(m), Box::new(n))),
+            (inner.clone(), inner.clone()).prop_map(|(m, n)| Shape::Add(Box::new(m), Box::new(n))),
+            (any::<u8>(), inner.clone(), inner.clone()).prop_map(|(x, m, n)| Shape::LetIn(
+                x,
+                Box::new(m),
+                Box::new(n)
+            )),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(m, n1, n2)| Shape::Ifte(
+                Box::new(m),
+                Box::new(n1),
+                Box::new(n2)
+            )),
+            (inner.clone(), inner).prop_map(|(m, n)| Shape::Try(Box::new(m), Box::new(n))),
+        ]
+    })
+}
+
+// binders get fresh names, variables pick one of the names in scope
+fn build(s: &Shape, scope: &mut Vec<Name>) -> Box<Term> {
+    let pos = Pos { line: 1, col: 1 };
+    let under = |xs: Vec<Name>, s: &Shape, scope: &mut Vec<Name>| {
+        let len = scope.len();
+        scope.extend(xs);
+        let m = build(s, scope);
+        scope.truncate(len);
+        m
+    };
+    match s {
+        Shape::Int(i) => int(*i as i32),
+        Shape::Var(_) if scope.is_empty() => int(0),
+        Shape::Var(i) => var(scope[*i as usize % scope.len()].clone()),
+        Shape::Fun(x, m) => {
+            let (f, x) = (Name::new(String::new()), Name::new(format!("x{}", x % 3)));
+            fun(f.clone(), x.clone(), under(vec![f, x], m, scope))
+        }
+        Shape::RecFun(m) => {
+            let (f, x) = (Name::new(String::from("f")), Name::new(String::from("x")));
+            fun(f.clone(), x.clone(), under(vec![f, x], m, scope))
+        }
+        Shape::App(m, n) => app(build(m, scope), build(n, scope), pos),
+        Shape::Add(m, n) => op2(Op2::Add, build(m, scope), build(n, scope), pos),
+        Shape::LetIn(x, m, n) => {
+            let x = Name::new(format!("x{}", x % 3));
+            letin(x.clone(), build(m, scope), under(vec![x], n, scope))
+        }
+        Shape::Ifte(m, n1, n2) => ifte(build(m, scope), build(n1, scope), build(n2, scope)),
+        Shape::Try(m, n) => {
+            let x = Name::new(String::from("e"));
+            trywith(build(m, scope), x.clone(), under(vec![x], n, scope))
+        }
+    }
+}
+
+fn subterms(m: &Term) -> Vec<&Term> {
+    use Term::*;
+    let mut ms = vec![m];
+    match m {
+        Fun(_, _, m) => ms.extend(subterms(m)),
+        App(m, n, _) | Op2(_, m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            ms.extend(subterms(m));
+            ms.extend(subterms(n))
+        }
+        Ifte(m, n1, n2) => {
+            ms.extend(subterms(m));
+            ms.extend(subterms(n1));
+            ms.extend(subterms(n2))
+        }
+        _ => (),
+    }
+    ms
+}
+
+fn free() -> Vec<Name> {
+    ["a", "b", "c"].map(|x| Name::new(String::from(x))).to_vec()
+}
+
+#[test]
+fn substitution_avoids_capture() {
+    let (x, y) = (Name::new(String::from("x")), Name::new(String::from("y")));
+    let f = Name::new(String::new());
+    // (fun y => x)[x := y] is fun y' => y, not fun y => y
+    let m = fun(f.clone(), y.clone(), var(x.clone()));
+    let m = substitute(&m, &x, &var(y.clone()));
+    assert_eq!(m.to_string(), "fun y' => y");
+    assert!(!alpha_eq(&m, &fun(f.clone(), y.clone(), var(y.clone()))));
+    assert_eq!(free_vars(&m), HashSet::from_iter([y.clone()]));
+    // shadowed occurrences are left alone
+    let m = letin(x.clone(), int(1), var(x.clone()));
+    assert!(alpha_eq(&substitute(&m, &x, &int(2)), &m));
+}
+
+proptest! {
+    #[test]
+    fn alpha_eq_up_to_binders(s in shape()) {
+        let mut scope = free();
+        let m = build(&s, &mut scope);
+        let n = build(&s, &mut scope);
+        prop_assert!(alpha_eq(&m, &m));
+        prop_assert!(alpha_eq(&m, &n));
+        prop_assert!(alpha_eq(&n, &m));
+        let mut scope = free();
+        let l = build(&s, &mut scope);
+        prop_assert_eq!(alpha_eq(&m, &l), free_vars(&m).is_empty());
+    }
+
+    #[test]
+    fn free_vars_of_substitution(s in shape(), i in any::<usize>(), j in any::<usize>()) {
+        let scope = free();
+        let m = build(&s, &mut scope.clone());
+        let ns = subterms(&m);
+        let n = ns[i % ns.len()];
+        let x = &scope[j % scope.len()];
+        let mut expected = free_vars(&m);
+        if expected.remove(x) {
+            expected.extend(free_vars(n));
+        }
+        prop_assert_eq!(free_vars(&substitute(&m, x, n)), expected);
+    }
+
+    #[test]
+    fn renaming_round_trip(s in shape(), j in any::<usize>()) {
+        let scope = free();
+        let m = build(&s, &mut scope.clone());
+        let x = &scope[j % scope.len()];
+        let z = Name::new(String::from("z"));
+        let n = substitute(&substitute(&m, x, &Term::Var(z.clone())), &z, &Term::Var(x.clone()));
+        prop_assert!(alpha_eq(&m, &n));
+    }
+
+    #[test]
+    fn substitution_lemma(s in shape(), i in any::<usize>(), j in any::<usize>()) {
+        let scope = free();
+        let (x, y) = (&scope[0], &scope[1]);
+        let m = build(&s, &mut scope.clone());
+        let ms = subterms(&m);
+        let (n, l) = (ms[i % ms.len()], ms[j % ms.len()]);
+        prop_assume!(!free_vars(l).contains(x));
+        let a = substitute(&substitute(&m, x, n), y, l);
+        let b = substitute(&substitute(&m, y, l), x, &substitute(n, y, l));
+        prop_assert!(alpha_eq(&a, &b));
+    }
+}
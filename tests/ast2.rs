@@ -0,0 +1,194 @@
+mod common;
+
+use ahash::HashMap;
+use rust_test::{
+    ast2::reduce,
+    error::{Error, Pos},
+    parse::parse_prog,
+    trans02,
+};
+use std::rc::Rc;
+
+fn run(src: &str) -> Result<String, Error> {
+    let tm = parse_prog(src).unwrap();
+    common::outcome(reduce(trans02::trans(Rc::new(HashMap::default()), &tm)?))
+}
+
+#[test]
+fn div_by_zero() {
+    let pos = Pos { line: 1, col: 3 };
+    assert_eq!(run("1 / 0"), Err(Error::DivByZero(pos)));
+    assert_eq!(run("1 % 0"), Err(Error::DivByZero(pos)));
+}
+
+#[test]
+fn div_overflow() {
+    let src = "let min := 0 - 2147483647 - 1 in\nmin / (0 - 1)";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 5 })));
+    let src = "let min := 0 - 2147483647 - 1 in\nmin % (0 - 1)";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 5 })));
+}
+
+#[test]
+fn arith_overflow() {
+    let pos = Pos { line: 1, col: 12 };
+    assert_eq!(run("2147483647 + 1"), Err(Error::Overflow(pos)));
+    assert_eq!(
+        run("0 - 2147483647 - 2"),
+        Err(Error::Overflow(Pos { line: 1, col: 16 }))
+    );
+    assert_eq!(
+        run("65536 * 32768"),
+        Err(Error::Overflow(Pos { line: 1, col: 7 }))
+    );
+    let src = "let min := 0 - 2147483647 - 1 in\n-min";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 1 })));
+    assert_eq!(run("2147483646 + 1"), Ok(String::from("Int(2147483647)")));
+}
+
+#[test]
+fn div_by_zero_in_callee() {
+    let src = "let f x := 10 / x in\nf 5 + f 0";
+    assert_eq!(run(src), Err(Error::DivByZero(Pos { line: 1, col: 15 })));
+}
+
+// the operand is reduced, not the whole term again
+#[test]
+fn unary_operators() {
+    assert_eq!(run("-(2 + 3) * 2"), Ok(String::from("Int(-10)")));
+    assert_eq!(run("if -1 < 0 then 1 else 2"), Ok(String::from("Int(1)")));
+}
+
+#[test]
+fn modulo() {
+    assert_eq!(run("17 % 5"), Ok(String::from("Int(2)")));
+    assert_eq!(run("(0 - 17) % 5"), Ok(String::from("Int(-2)")));
+    assert_eq!(run("2 + 17 % 5 * 3"), Ok(String::from("Int(8)")));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(run("1.5 * 2.0 - 0.5"), Ok(String::from("Float(2.5)")));
+    assert_eq!(run("7.5 % 2.0"), Ok(String::from("Float(1.5)")));
+    assert_eq!(run("1.0 / 0.0"), Ok(String::from("Float(inf)")));
+    assert_eq!(run("2.5e3 <= 2500.0"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("int_of_float (0.0 - 3.7)"), Ok(String::from("Int(-3)")));
+    assert_eq!(run("float_of_int 3 / 2.0"), Ok(String::from("Float(1.5)")));
+}
+
+#[test]
+fn float_round_trip() {
+    for src in ["0.1 + 0.2", "1.0e300 * 10.0", "1.0 / 3.0e7", "2.0 * 3.0"] {
+        let printed = run(src).unwrap();
+        let lit = &printed["Float(".len()..printed.len() - 1];
+        assert_eq!(run(lit), Ok(printed.clone()));
+    }
+}
+
+#[test]
+fn short_circuit() {
+    let src = "let x := 0 in x != 0 && 10 / x > 1";
+    assert_eq!(run(src), Ok(String::from("Bool(false)")));
+    let src = "let x := 0 in x == 0 || 10 / x > 1";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+    let src = "let rec loop x := loop x in false && loop 0";
+    assert_eq!(run(src), Ok(String::from("Bool(false)")));
+    let src = "let rec loop x := loop x in true || loop 0";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+    assert_eq!(run("true && 1 < 2"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("false || 1 > 2"), Ok(String::from("Bool(false)")));
+}
+
+#[test]
+fn equality() {
+    assert_eq!(run("true == false"), Ok(String::from("Bool(false)")));
+    assert_eq!(run("true != false"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("(1 < 2) == (3 < 4)"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("false < true"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("0.5 == 0.5"), Ok(String::from("Bool(true)")));
+    let src = "let nan := 0.0 / 0.0 in nan != nan && (! (nan <= nan))";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+}
+
+#[test]
+fn equality_errors() {
+    let pos = Pos { line: 1, col: 3 };
+    assert_eq!(run("1 == true"), Err(Error::Incomparable(pos)));
+    assert_eq!(run("1 <= 1.0"), Err(Error::Incomparable(pos)));
+    let src = "let f x := x in\nf == f";
+    let pos = Pos { line: 2, col: 3 };
+    assert_eq!(run(src), Err(Error::FunctionalValue(pos)));
+}
+
+#[test]
+fn references() {
+    let src = "
+let counter := ref 0 in
+let tick u := counter := !counter + 1 in
+let rec loop n := if n <= 0 then !counter else let t := tick 0 in loop (n - 1) in
+loop 10";
+    assert_eq!(run(src), Ok(String::from("Int(10)")));
+    let src = "let r := ref 1 in let s := r in let u := s := 2 in !r";
+    assert_eq!(run(src), Ok(String::from("Int(2)")));
+    let src = "let r := ref 1 in let f x := x + 1 in f (!r) * 10";
+    assert_eq!(run(src), Ok(String::from("Int(20)")));
+    assert_eq!(run("ref 1 == ref 1"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("!true != false"), Ok(String::from("Bool(false)")));
+    // ! is negation, except right before a variable or a parenthesis
+    let src = "let b := ref false in ! !b";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+}
+
+#[test]
+fn exceptions() {
+    assert_eq!(
+        run("try 1 + raise 2 with e => e * 10"),
+        Ok(String::from("Int(20)"))
+    );
+    assert_eq!(
+        run("try 1 + 2 with e => e * 10"),
+        Ok(String::from("Int(3)"))
+    );
+    assert_eq!(run("try 1 / 0 with e => e"), Ok(String::from("Int(1)")));
+    let src = "try (try raise 1 with e => raise (e + 1)) with e => e";
+    assert_eq!(run(src), Ok(String::from("Int(2)")));
+    let src = "let f x := if x == 0 then raise true else x in\nf 0";
+    let pos = Pos { line: 1, col: 27 };
+    assert_eq!(
+        run(src),
+        Err(Error::Uncaught(String::from("Bool(true)"), pos))
+    );
+}
+
+#[test]
+fn exceptions_restore_env() {
+    let src = "
+let rec f n :=
+  let y := n in
+  let r := try (if n == 0 then raise 0 else f (n - 1)) with e => 100 in
+  y + n * 10 + r
+in f 1";
+    assert_eq!(run(src), Ok(String::from("Int(111)")));
+}
+
+#[test]
+fn effects_unsupported() {
+    let src = "effect get in fun x => handle perform get 0 with | return v => v";
+    assert_eq!(
+        run(src),
+        Err(Error::Unsupported(String::from(
+            "effect handlers in the higher-order translation"
+        )))
+    );
+}
+
+#[test]
+fn callcc_unsupported() {
+    let src = "1 + callcc (fun k => k 2)";
+    assert_eq!(
+        run(src),
+        Err(Error::Unsupported(String::from(
+            "callcc in the higher-order translation"
+        )))
+    );
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use ahash::HashMap;
+use rust_test::{
+    cek::{self, Machine},
+    error::Error,
+    eval::eval,
+};
+use std::{cell::RefCell, fs, rc::Rc};
+
+fn run(src: &str) -> Result<String, Error> {
+    common::outcome(cek::run(Rc::new(HashMap::default()), &common::source(src)))
+}
+
+#[test]
+fn callcc_escape() {
+    assert_eq!(
+        run("1 + callcc (fun k => 10 + k 5)"),
+        Ok(String::from("Int(6)"))
+    );
+    assert_eq!(run("1 + callcc (fun k => 10)"), Ok(String::from("Int(11)")));
+    let src = "
+let f i := 10 - i in
+callcc (fun k =>
+  let rec prod i :=
+    if i > 20 then 1 else
+    let x := f i in
+    if x == 0 then k 0 else x * prod (i + 1)
+  in prod 1)";
+    assert_eq!(run(src), Ok(String::from("Int(0)")));
+}
+
+#[test]
+fn callcc_reentry() {
+    let src = "
+let saved := ref (fun x => x) in
+let count := ref 0 in
+let r := callcc (fun k => let u := saved := k in 0) in
+let u := count := !count + 1 in
+if !count < 3 then !saved (r + 10) else r";
+    assert_eq!(run(src), Ok(String::from("Int(20)")));
+}
+
+#[test]
+fn callcc_through_try() {
+    let src = "try callcc (fun k => try k 1 with e => 2) + raise 10 with e => e";
+    assert_eq!(run(src), Ok(String::from("Int(10)")));
+}
+
+#[test]
+fn cps_matches_direct_style() {
+    let direct = fs::read_to_string("examples/test2.txt").unwrap();
+    let cps = fs::read_to_string("examples/test3.txt").unwrap();
+    assert_eq!(run(&direct), run(&cps));
+    let callcc = "
+let rec mccarthy n :=
+  callcc (fun k =>
+    if n <= 100 then k (mccarthy (mccarthy (n + 11))) else k (n - 10))
+in mccarthy 23";
+    assert_eq!(run(&direct), run(callcc));
+}
+
+#[test]
+fn examples_agree_with_eval() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        // church-numeral factorial, takes seconds on either backend
+        if path.ends_with("test1.txt") {
+            continue;
+        }
+        let src = fs::read_to_string(path).unwrap();
+        let tm = common::source(&src);
+        let expected = common::outcome(eval(&RefCell::new(HashMap::default()), &tm));
+        let actual = common::outcome(cek::run(Rc::new(HashMap::default()), &tm));
+        assert_eq!(expected.ok(), actual.ok());
+    }
+}
+
+#[test]
+fn deep_recursion() {
+    let src = "let rec count n := if n == 0 then 0 else 1 + count (n - 1) in count 1000000";
+    assert_eq!(run(src), Ok(String::from("Int(1000000)")));
+}
+
+#[test]
+fn single_step_and_snapshot() {
+    let tm = common::source("let f x := x * 2 in f 3 + f 4");
+    let mut machine = Machine::new(Rc::new(HashMap::default()), &tm);
+    for _ in 0..10 {
+        assert!(machine.step().is_none());
+    }
+    let mut snapshot = machine.clone();
+    let result = common::outcome(machine.run());
+    assert_eq!(result.ok(), Some(String::from("Int(14)")));
+    assert_eq!(snapshot.steps, 10);
+    let mut steps = 10;
+    while snapshot.step().is_none() {
+        steps += 1;
+    }
+    assert_eq!(steps, machine.steps);
+    assert!(snapshot.step().is_some());
+}
@@ -0,0 +1,169 @@
+mod common;
+mod generator;
+
+use ahash::HashMap;
+use common::{example, resolve, show, source};
+use proptest::prelude::*;
+use rust_test::{
+    ast3::{self, Prog, Term, Value},
+    error::Error,
+    names::Name,
+    trans13,
+};
+
+fn convert(src: &str) -> Prog {
+    trans13::trans(&source(src)).unwrap()
+}
+
+// every variable is bound in the function it occurs in, and fields are
+// only read from its own closure and within the captured variables
+fn closed(prog: &Prog, clo: Option<(&Name, usize)>, bound: &mut Vec<Name>, m0: &Term) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => true,
+        Var(x) => bound.contains(x),
+        Field(c, i) => clo.is_some_and(|(d, n)| c == d && *i < n),
+        Op1(_, m, _) | Raise(m, _) => closed(prog, clo, bound, m),
+        Op2(_, m, n, _) | App(m, n, _) => {
+            closed(prog, clo, bound, m) && closed(prog, clo, bound, n)
+        }
+        Clo(code, env) => {
+            *code < prog.funs.len() && env.iter().all(|m| closed(prog, clo, bound, m))
+        }
+        LetIn(x, m, n) | Try(m, x, n) => {
+            if !closed(prog, clo, bound, m) {
+                return false;
+            }
+            bound.push(x.clone());
+            let result = closed(prog, clo, bound, n);
+            bound.pop();
+            result
+        }
+        Ifte(m, n1, n2) => {
+            closed(prog, clo, bound, m)
+                && closed(prog, clo, bound, n1)
+                && closed(prog, clo, bound, n2)
+        }
+    }
+}
+
+// the number of fields each function reads, from the closures built for it
+fn lifted(prog: &Prog) -> bool {
+    let mut sizes = HashMap::default();
+    fn collect(m0: &Term, sizes: &mut HashMap<usize, usize>) {
+        use Term::*;
+        match m0 {
+            Int(_) | Float(_) | Bool(_) | Var(_) | Field(..) => (),
+            Op1(_, m, _) | Raise(m, _) => collect(m, sizes),
+            Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+                collect(m, sizes);
+                collect(n, sizes)
+            }
+            Clo(code, env) => {
+                sizes.insert(*code, env.len());
+                env.iter().for_each(|m| collect(m, sizes))
+            }
+            Ifte(m, n1, n2) => {
+                collect(m, sizes);
+                collect(n1, sizes);
+                collect(n2, sizes)
+            }
+        }
+    }
+    collect(&prog.main, &mut sizes);
+    prog.funs
+        .iter()
+        .for_each(|def| collect(&def.body, &mut sizes));
+    closed(prog, None, &mut vec![], &prog.main)
+        && prog.funs.iter().enumerate().all(|(i, def)| {
+            let clo = Some((&def.clo, sizes[&i]));
+            closed(
+                prog,
+                clo,
+                &mut vec![def.clo.clone(), def.param.clone()],
+                &def.body,
+            )
+        })
+}
+
+fn run(src: &str) -> String {
+    show(ast3::run(&convert(src)))
+}
+
+#[test]
+fn captured_variables() {
+    let prog = convert("let x := 1 in let y := 2 in let f z := x + z in f y");
+    assert_eq!(prog.funs.len(), 1);
+    assert_eq!(
+        prog.to_string(),
+        "fun f clo z := clo.0 + z\nlet x := 1 in let y := 2 in let f := <f x> in f y"
+    );
+    assert!(lifted(&prog));
+}
+
+#[test]
+fn nested_functions() {
+    let prog = convert("let add x y := x + y in add 1 2");
+    assert_eq!(
+        prog.to_string(),
+        "fun lambda clo y := clo.0 + y\nfun add clo x := <lambda x>\nlet add := <add> in (add 1) 2"
+    );
+    // a field of the outer closure is copied into the inner one
+    let prog = convert("fun a => fun b => fun c => a + b + c");
+    assert_eq!(
+        prog.to_string(),
+        "fun lambda2 clo c := (clo.0 + clo.1) + c\n\
+         fun lambda1 clo b := <lambda2 clo.0 b>\n\
+         fun lambda clo a := <lambda1 a>\n\
+         <lambda>"
+    );
+    assert!(lifted(&prog));
+}
+
+#[test]
+fn recursion_through_the_closure() {
+    let prog = trans13::trans(&example("test3")).unwrap();
+    assert!(lifted(&prog));
+    assert_eq!(format!("{:?}", ast3::run(&prog)), "Ok(Int(91))");
+    let src = "let n := 10 in let rec sum i := if i > n then 0 else i + sum (i + 1) in sum 1";
+    assert_eq!(run(src), "Int(55)");
+}
+
+#[test]
+fn closures_are_values() {
+    assert_eq!(
+        run("let f x := x in f == f"),
+        "Error(FunctionalValue(Pos { line: 1, col: 19 }))"
+    );
+    let src = "let r := ref (fun x => x) in let k := 3 in let u := r := (fun x => x * k) in !r 5";
+    assert_eq!(run(src), "Int(15)");
+    let src = "let f x := if x then raise 1 else 2 in try f true with e => e + f false";
+    assert_eq!(run(src), "Int(3)");
+    match ast3::run(&convert("let k := 2 in fun x => x + k")) {
+        Ok(Value::Clo(clo)) => assert_eq!(format!("{:?}", clo.env), "[Int(2)]"),
+        v => panic!("{:?}", v),
+    }
+}
+
+#[test]
+fn unsupported() {
+    assert_eq!(
+        trans13::trans(&example("test8")).err(),
+        Some(Error::Unsupported(String::from(
+            "effect handlers in closure conversion"
+        )))
+    );
+    assert_eq!(
+        trans13::trans(&source("1 + callcc (fun k => k 1)")).err(),
+        Some(Error::Unsupported(String::from(
+            "callcc in closure conversion"
+        )))
+    );
+}
+
+proptest! {
+    #[test]
+    fn every_function_is_lifted(m in generator::program(false)) {
+        prop_assert!(lifted(&trans13::trans(&resolve(&m)).unwrap()));
+    }
+}
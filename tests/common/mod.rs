@@ -0,0 +1,37 @@
+// fixtures shared by the test crates, programs are parsed and resolved by
+// trans01 here and each crate uses some of them
+#![allow(dead_code)]
+use rust_test::{
+    ast0,
+    ast1::Term,
+    error::{Error, Exn},
+    parse::parse_prog,
+    trans01,
+};
+use std::{fmt::Debug, fs};
+
+// resolves the names of a generated program
+pub fn resolve(m: &ast0::Term) -> Box<Term> {
+    trans01::trans(&mut trans01::Ctx::default(), m)
+}
+
+pub fn source(src: &str) -> Box<Term> {
+    resolve(&parse_prog(src).unwrap())
+}
+
+// examples/NAME.txt
+pub fn example(name: &str) -> Box<Term> {
+    source(&fs::read_to_string(format!("examples/{}.txt", name)).unwrap())
+}
+
+pub fn show<V: Debug>(result: Result<V, Exn<V>>) -> String {
+    match result {
+        Ok(v) => format!("{:?}", v),
+        Err(e) => format!("{:?}", e),
+    }
+}
+
+// the value or the error of a run, for tests that match on the error
+pub fn outcome<V: Debug>(result: Result<V, Exn<V>>) -> Result<String, Error> {
+    result.map(|v| format!("{:?}", v)).map_err(Error::from)
+}
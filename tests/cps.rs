@@ -0,0 +1,176 @@
+mod common;
+
+use ahash::HashMap;
+use common::{example, show, source};
+use rust_test::{
+    ast1::{self, alpha_eq},
+    ast5::{self, Atom, Cont, Term},
+    error::{Error, Pos},
+    eval::eval,
+    names::Name,
+    trans15,
+};
+use std::cell::RefCell;
+
+const POS: Pos = Pos { line: 0, col: 0 };
+
+fn run(src: &str) -> String {
+    show(ast5::run(&trans15::trans(&source(src)).unwrap()))
+}
+
+fn anonymous(x: Name, m: Box<ast1::Term>) -> Box<ast1::Term> {
+    ast1::fun(Name::new(String::new()), x, m)
+}
+
+// back to a direct style term that takes its continuations as arguments,
+// the program is returned to the identity
+fn curried(m0: &Term) -> Box<ast1::Term> {
+    use Term::*;
+    match m0 {
+        Let(x, a, m) => ast1::letin(x.clone(), atom(a), curried(m)),
+        LetCont(j, x, m, n) => ast1::letin(j.clone(), anonymous(x.clone(), curried(m)), curried(n)),
+        App(a, b, c, pos) => ast1::app(ast1::app(atom(a), atom(b), *pos), cont(c), POS),
+        Ret(c, a) => ast1::app(cont(c), atom(a), POS),
+        Ifte(a, m1, m2) => ast1::ifte(atom(a), curried(m1), curried(m2)),
+        Raise(a, pos) => ast1::raise(atom(a), *pos),
+        Catch(..) | Uncatch(..) => panic!("curried({:?})", m0),
+    }
+}
+
+fn atom(a: &Atom) -> Box<ast1::Term> {
+    match a {
+        Atom::Int(i) => ast1::int(*i),
+        Atom::Float(f) => ast1::float(*f),
+        Atom::Bool(b) => ast1::bool(*b),
+        Atom::Var(x) => ast1::var(x.clone()),
+        Atom::Op1(op, a, pos) => ast1::op1(*op, atom(a), *pos),
+        Atom::Op2(op, a, b, pos) => ast1::op2(*op, atom(a), atom(b), *pos),
+        Atom::Fun(f, x, k, m) => ast1::fun(f.clone(), x.clone(), anonymous(k.clone(), curried(m))),
+    }
+}
+
+fn cont(c: &Cont) -> Box<ast1::Term> {
+    match c {
+        Cont::Var(k) => ast1::var(k.clone()),
+        Cont::Fun(x, m) => anonymous(x.clone(), curried(m)),
+        Cont::Halt => {
+            let x = Name::new(String::from("x"));
+            anonymous(x.clone(), ast1::var(x))
+        }
+    }
+}
+
+// the function bound by the outermost let
+fn definition(m: &ast1::Term) -> &ast1::Term {
+    match m {
+        ast1::Term::LetIn(_, m, _) => m,
+        _ => panic!("definition({})", m),
+    }
+}
+
+#[test]
+fn mccarthy_becomes_kmccarthy() {
+    let test2 = example("test2");
+    let test3 = example("test3");
+    let cps = trans15::trans(&test2).unwrap();
+    assert_eq!(
+        cps.to_string(),
+        "let rec mccarthy n _k := if n <= 100 then mccarthy (n + 11) (fun _1 => mccarthy _1 _k) \
+         else _k (n - 10) in mccarthy 23 halt"
+    );
+    let cps = curried(&cps);
+    assert!(alpha_eq(definition(&cps), definition(&test3)));
+    let env = RefCell::new(HashMap::default());
+    let expected = format!("{:?}", eval(&env, &test2).unwrap());
+    assert_eq!(format!("{:?}", eval(&env, &cps).unwrap()), expected);
+    assert_eq!(
+        format!("{:?}", ast5::run(&trans15::trans(&test2).unwrap()).unwrap()),
+        expected
+    );
+    assert_eq!(
+        format!("{:?}", ast5::run(&trans15::trans(&test3).unwrap()).unwrap()),
+        expected
+    );
+}
+
+#[test]
+fn no_administrative_redexes() {
+    let cps = trans15::trans(&source("let f x := x + 1 in f (f 1) * 2")).unwrap();
+    assert_eq!(
+        cps.to_string(),
+        "let f := fun x _k => _k (x + 1) in f 1 (fun _1 => f _1 (fun _2 => halt (_2 * 2)))"
+    );
+    // a continuation used by both branches is bound once
+    let cps = trans15::trans(&source("let f x := x in f (if true then f 1 else 2) + 3")).unwrap();
+    assert_eq!(
+        cps.to_string(),
+        "let f := fun x _k => _k x in let cont _3 _1 := f _1 (fun _2 => halt (_2 + 3)) in \
+         if true then f 1 _3 else _3 2"
+    );
+}
+
+#[test]
+fn evaluation_order() {
+    let src = "let r := ref 0 in
+let tick u := r := !r * 10 + u in
+let f x y := x + y in
+let v := f (tick 1) (tick 2) + tick 3 in !r";
+    assert_eq!(run(src), "Int(123)");
+    // the division fails before f is called
+    let src = "let r := ref 0 in let f x := r := x in try (1 / 0) + f 1 with e => !r";
+    assert_eq!(run(src), "Int(0)");
+    let src = "let r := ref 0 in let f x := r := x in try (1 / 1) + f 1 with e => !r";
+    assert_eq!(run(src), "Int(2)");
+    assert_eq!(run("false && 1 / 0 == 0"), "Bool(false)");
+    assert_eq!(run("true || 1 / 0 == 0"), "Bool(true)");
+}
+
+#[test]
+fn exceptions() {
+    assert_eq!(run("try 1 + raise 2 with x => x * 10"), "Int(20)");
+    assert_eq!(run("try 1 / 0 with x => x"), "Int(1)");
+    let src = "let f x := if x == 0 then raise 7 else x in
+try (try f 0 with x => f (x - 7)) + 1 with y => y + 100";
+    assert_eq!(run(src), "Int(107)");
+    // leaving a try removes its handler
+    let src = "let r := try 1 with x => 2 in try raise r with x => x + 10";
+    assert_eq!(run(src), "Int(11)");
+    assert_eq!(
+        run("let x := try 1 with y => y in raise x"),
+        "Raise(Int(1), Pos { line: 1, col: 31 })"
+    );
+    assert_eq!(
+        run("let f x := 1 / x in f 1 + f 0"),
+        "Error(DivByZero(Pos { line: 1, col: 14 }))"
+    );
+}
+
+#[test]
+fn tail_calls_only() {
+    // a loop far deeper than the native stack runs in constant space
+    let src = "let rec loop n := if n == 0 then 0 else loop (n - 1) in loop 1000000";
+    let tm = trans15::trans(&source(src)).unwrap();
+    let mut machine = ast5::Machine::new(&tm);
+    let result = loop {
+        if let Some(result) = machine.step() {
+            break result;
+        }
+        assert!(machine.handlers.is_empty());
+    };
+    assert_eq!(format!("{:?}", result), "Ok(Int(0))");
+    assert_eq!(machine.steps, 2_000_004);
+}
+
+#[test]
+fn unsupported() {
+    assert_eq!(
+        trans15::trans(&example("test8")).err(),
+        Some(Error::Unsupported(String::from(
+            "effect handlers in cps conversion"
+        )))
+    );
+    assert_eq!(
+        trans15::trans(&source("1 + callcc (fun k => k 1)")).err(),
+        Some(Error::Unsupported(String::from("callcc in cps conversion")))
+    );
+}
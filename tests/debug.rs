@@ -0,0 +1,75 @@
+mod common;
+
+use rust_test::debug::Debugger;
+
+#[test]
+fn mccarthy_session() {
+    let tm = common::example("test2");
+    let mut d = Debugger::new(&tm);
+    assert_eq!(d.command("stack"), "no function calls");
+    assert_eq!(d.command("break mccarthy"), "breakpoint 1 on mccarthy");
+    assert!(
+        d.command("continue")
+            .starts_with("breakpoint 1, mccarthy 23 called at 6:13")
+    );
+    assert!(
+        d.command("continue")
+            .starts_with("breakpoint 1, mccarthy 34 called at 3:24")
+    );
+    assert_eq!(
+        d.command("stack"),
+        "#0 mccarthy 34 called at 3:24\n#1 mccarthy 23 called at 6:13"
+    );
+    assert_eq!(d.command("env"), "mccarthy = <mccarthy>\nn = 34");
+    assert_eq!(d.command("print n"), "34");
+    assert_eq!(d.command("print m"), "m is not in scope");
+    assert_eq!(d.command("step"), "2:8  n <= 100");
+    assert_eq!(d.command("step"), "2:8  n");
+    assert_eq!(d.command("next"), "2:8  100");
+    d.command("delete");
+    assert_eq!(d.command("break 5"), "breakpoint 1 on 5");
+    assert_eq!(d.command("continue"), "breakpoint 1, line 5\n5:7  n - 10");
+    assert_eq!(d.command("print n"), "111");
+    assert_eq!(d.stack.calls.len(), 9);
+    assert_eq!(d.command("finish"), "mccarthy returned 101");
+    d.command("delete");
+    assert_eq!(d.command("continue"), "value : 91");
+    assert_eq!(d.command("step"), "the program has finished");
+}
+
+#[test]
+fn errors_end_the_session() {
+    let tm = common::source("let f x := 10 / x in\nf 0");
+    let mut d = Debugger::new(&tm);
+    d.command("break f");
+    assert_eq!(
+        d.command("continue"),
+        "breakpoint 1, f 0 called at 2:3\n1:15  10 / x"
+    );
+    assert_eq!(
+        d.command("finish"),
+        "runtime error : 1:15: division by zero"
+    );
+}
+
+#[test]
+fn tail_calls_reuse_the_entry() {
+    let src = "let rec loop i :=\n  if i == 0 then 0 else loop (i - 1)\nin\nloop 3";
+    let tm = common::source(src);
+    let mut d = Debugger::new(&tm);
+    assert_eq!(
+        d.command("break 3"),
+        "line 3 has no operator or call to stop on"
+    );
+    assert_eq!(d.command("break loop"), "breakpoint 1 on loop");
+    d.command("continue");
+    d.command("continue");
+    assert!(
+        d.command("continue")
+            .starts_with("breakpoint 1, loop 1 called at 2:30")
+    );
+    assert_eq!(d.command("stack"), "#0 loop 1 called at 2:30");
+    d.command("delete");
+    assert_eq!(d.command("finish"), "loop returned 0");
+    assert_eq!(d.command("stack"), "no function calls");
+}
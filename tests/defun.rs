@@ -0,0 +1,144 @@
+mod common;
+mod generator;
+
+use common::{example, resolve, source};
+use proptest::prelude::*;
+use rust_test::{
+    ast6::{self, Prog, Term},
+    error::Error,
+    names::Name,
+    trans16,
+};
+
+fn convert(src: &str) -> Prog {
+    trans16::trans(&source(src)).unwrap()
+}
+
+// constructors are shown by name rather than by code
+fn run(src: &str) -> String {
+    let prog = convert(src);
+    match ast6::run(&prog) {
+        Ok(v) => prog.show(&v),
+        Err(e) => format!("{:?}", e),
+    }
+}
+
+// every variable is bound in the case it occurs in and every constructor
+// is given as many fields as its case expects
+fn closed(prog: &Prog, bound: &mut Vec<Name>, m0: &Term) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => true,
+        Var(x) => bound.contains(x),
+        Op1(_, m, _) | Raise(m, _) => closed(prog, bound, m),
+        Op2(_, m, n, _) | Apply(m, n, _) => closed(prog, bound, m) && closed(prog, bound, n),
+        Con(tag, xs) => {
+            prog.cases
+                .get(*tag)
+                .is_some_and(|case| case.fields.len() == xs.len())
+                && xs.iter().all(|x| bound.contains(x))
+        }
+        LetIn(x, m, n) | Try(m, x, n) => {
+            if !closed(prog, bound, m) {
+                return false;
+            }
+            bound.push(x.clone());
+            let result = closed(prog, bound, n);
+            bound.pop();
+            result
+        }
+        Ifte(m, n1, n2) => {
+            closed(prog, bound, m) && closed(prog, bound, n1) && closed(prog, bound, n2)
+        }
+    }
+}
+
+fn first_order(prog: &Prog) -> bool {
+    prog.cases.iter().all(|case| {
+        let mut bound = case.fields.clone();
+        bound.push(case.func.clone());
+        bound.push(case.param.clone());
+        closed(prog, &mut bound, &case.body)
+    }) && closed(prog, &mut vec![], &prog.main)
+}
+
+#[test]
+fn one_constructor_per_function() {
+    let prog = convert("let x := 1 in let y := 2 in let f z := x + z in f y");
+    assert_eq!(
+        prog.to_string(),
+        "apply (F x as clo) z := x + z\nlet x := 1 in let y := 2 in let f := F x in apply f y"
+    );
+    let prog = convert("fun a => fun b => fun c => a + b + c");
+    assert_eq!(
+        prog.to_string(),
+        "apply (Lambda2 a b as clo) c := (a + b) + c\n\
+         apply (Lambda1 a as clo) b := Lambda2 a b\n\
+         apply (Lambda as clo) a := Lambda1 a\n\
+         Lambda"
+    );
+    assert!(first_order(&prog));
+}
+
+#[test]
+fn recursion_through_apply() {
+    let prog = convert("let rec loop n := if n == 0 then 0 else loop (n - 1) in loop 10");
+    assert_eq!(
+        prog.to_string(),
+        "apply (Loop as loop) n := if n == 0 then 0 else apply loop (n - 1)\n\
+         let loop := Loop in apply loop 10"
+    );
+    let prog = trans16::trans(&example("test3")).unwrap();
+    assert_eq!(prog.show(&ast6::run(&prog).unwrap()), "91");
+    let src = "let n := 10 in let rec sum i := if i > n then 0 else i + sum (i + 1) in sum 1";
+    assert_eq!(run(src), "55");
+}
+
+#[test]
+fn closures_are_data() {
+    assert_eq!(run("let k := 2 in fun x => x + k"), "Lambda(2)");
+    assert_eq!(run("let add x y := x + y in add 1"), "Lambda(1)");
+    assert_eq!(run("let f x := x in let g y := f in g 0"), "F");
+    assert_eq!(
+        run("let k := 2 in let r := ref 1 in fun x => x + k + !r"),
+        "Lambda(2, ref 1)"
+    );
+    assert_eq!(
+        run("let f x := x in f == f"),
+        "Error(FunctionalValue(Pos { line: 1, col: 19 }))"
+    );
+}
+
+#[test]
+fn exceptions() {
+    let src = "let f x := if x then raise 1 else 2 in try f true with e => e + f false";
+    assert_eq!(run(src), "3");
+    assert_eq!(run("try 1 / 0 with x => x"), "1");
+    assert_eq!(
+        run("let f x := raise x in f 4"),
+        "Raise(Int(4), Pos { line: 1, col: 12 })"
+    );
+}
+
+#[test]
+fn unsupported() {
+    assert_eq!(
+        trans16::trans(&example("test8")).err(),
+        Some(Error::Unsupported(String::from(
+            "effect handlers in defunctionalization"
+        )))
+    );
+    assert_eq!(
+        trans16::trans(&source("1 + callcc (fun k => k 1)")).err(),
+        Some(Error::Unsupported(String::from(
+            "callcc in defunctionalization"
+        )))
+    );
+}
+
+proptest! {
+    #[test]
+    fn every_function_is_a_constructor(m in generator::program(false)) {
+        prop_assert!(first_order(&trans16::trans(&resolve(&m)).unwrap()));
+    }
+}
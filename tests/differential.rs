@@ -0,0 +1,135 @@
+mod common;
+mod generator;
+
+use ahash::HashMap;
+use proptest::prelude::*;
+use rust_test::{
+    ast0, ast1, ast2, ast3, ast5, ast6, cek,
+    error::{Error, Exn},
+    eval::eval,
+    lazy, opt, smallstep, trans02, trans13, trans14, trans15, trans16,
+};
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+const MAX_STEPS: usize = 100_000;
+
+// closures and lambda terms print differently
+fn value<V: Debug>(v: &V) -> String {
+    let v = format!("{:?}", v);
+    match v.starts_with("Clo(") || v.starts_with("Fun") {
+        true => String::from("<fun>"),
+        false => v,
+    }
+}
+
+fn show<V: Debug>(result: Result<V, Exn<V>>) -> String {
+    match result {
+        Ok(v) => value(&v),
+        Err(Exn::Raise(v, pos)) => format!("runtime error : {}", Error::Uncaught(value(&v), pos)),
+        Err(Exn::Error(e)) => format!("runtime error : {}", e),
+    }
+}
+
+fn reduce(tm: &ast1::Term) -> Option<String> {
+    let mut m = Box::new(tm.clone());
+    for _ in 0..MAX_STEPS {
+        match smallstep::step(&m) {
+            Ok(Some(m1)) => m = m1,
+            Ok(None) => {
+                return Some(match *m {
+                    ast1::Term::Raise(v, pos) => show(Err(Exn::Raise(v, pos))),
+                    m => show::<ast1::Term>(Ok(m)),
+                });
+            }
+            Err(e) => return Some(show::<ast1::Term>(Err(e.into()))),
+        }
+    }
+    None
+}
+
+fn cek(tm: &ast1::Term) -> Option<String> {
+    let mut machine = cek::Machine::new(Rc::new(HashMap::default()), tm);
+    (0..MAX_STEPS).find_map(|_| machine.step()).map(show)
+}
+
+fn uses_try(m: &ast0::Term) -> bool {
+    use ast0::Term::*;
+    match m {
+        Int(_) | Float(_) | Bool(_) | Var(_) => false,
+        Try(..) => true,
+        Op1(_, m, _)
+        | Fun(_, _, m)
+        | Raise(m, _)
+        | Effect(_, m)
+        | Perform(_, m, _)
+        | CallCC(m, _) => uses_try(m),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) => uses_try(m) || uses_try(n),
+        Ifte(m, n1, n2) => uses_try(m) || uses_try(n1) || uses_try(n2),
+        Handle(m, _, n, cases) => {
+            uses_try(m) || uses_try(n) || cases.iter().any(|(.., m)| uses_try(m))
+        }
+    }
+}
+
+// ill-typed programs get stuck, which every backend reports with a panic
+fn stuck(f: impl FnOnce() -> Option<String>) -> Option<String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result.unwrap_or(Some(String::from("stuck")))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1000))]
+
+    #[test]
+    fn strict_backends_agree(m in generator::program(true)) {
+        let tm = common::resolve(&m);
+        let expected = show(eval(&RefCell::new(HashMap::default()), &tm));
+        prop_assert_eq!(Some(expected.clone()), cek(&tm));
+        prop_assert_eq!(Some(expected.clone()), reduce(&tm));
+        let tm2 = trans02::trans(Rc::new(HashMap::default()), &m).unwrap();
+        prop_assert_eq!(&expected, &show(ast2::reduce(tm2)));
+        prop_assert_eq!(&expected, &show(ast3::run(&trans13::trans(&tm).unwrap())));
+        let anf = trans14::untrans(&trans14::trans(&tm));
+        prop_assert_eq!(&expected, &show(eval(&RefCell::new(HashMap::default()), &anf)));
+        prop_assert_eq!(&expected, &show(ast5::run(&trans15::trans(&tm).unwrap())));
+        prop_assert_eq!(&expected, &show(ast6::run(&trans16::trans(&tm).unwrap())));
+        for level in [1, 2] {
+            let opt = opt::optimize(&tm, level);
+            prop_assert_eq!(&expected, &show(eval(&RefCell::new(HashMap::default()), &opt)));
+        }
+    }
+
+    // without try, a program that runs without error evaluates a superset
+    // of what a lazy strategy forces
+    #[test]
+    fn lazy_agrees_on_values(m in generator::program(true)) {
+        let tm = common::resolve(&m);
+        let expected = eval(&RefCell::new(HashMap::default()), &tm);
+        if uses_try(&m) || expected.is_err() {
+            return Ok(());
+        }
+        let expected = show(expected);
+        let env = Rc::new(HashMap::default());
+        prop_assert_eq!(&expected, &show(lazy::eval(&env, &tm)));
+        prop_assert_eq!(&expected, &show(lazy::eval_by_name(&env, &tm)));
+    }
+
+    // untyped programs may diverge, only step limited backends run them
+    #[test]
+    fn untyped_machines_agree(m in generator::program(false)) {
+        let tm = common::resolve(&m);
+        let expected = stuck(|| cek(&tm));
+        let actual = stuck(|| reduce(&tm));
+        if let (Some(expected), Some(actual)) = (expected, actual) {
+            prop_assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+mod common;
+
+use ahash::HashMap;
+use rust_test::{
+    error::{Error, Pos},
+    eval::*,
+};
+use std::cell::RefCell;
+
+fn run(src: &str) -> Result<String, Error> {
+    common::outcome(eval(
+        &RefCell::new(HashMap::default()),
+        &common::source(src),
+    ))
+}
+
+#[test]
+fn div_by_zero() {
+    let pos = Pos { line: 1, col: 3 };
+    assert_eq!(run("1 / 0"), Err(Error::DivByZero(pos)));
+    assert_eq!(run("1 % 0"), Err(Error::DivByZero(pos)));
+}
+
+#[test]
+fn div_overflow() {
+    let src = "let min := 0 - 2147483647 - 1 in\nmin / (0 - 1)";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 5 })));
+    let src = "let min := 0 - 2147483647 - 1 in\nmin % (0 - 1)";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 5 })));
+}
+
+#[test]
+fn arith_overflow() {
+    let pos = Pos { line: 1, col: 12 };
+    assert_eq!(run("2147483647 + 1"), Err(Error::Overflow(pos)));
+    assert_eq!(
+        run("0 - 2147483647 - 2"),
+        Err(Error::Overflow(Pos { line: 1, col: 16 }))
+    );
+    assert_eq!(
+        run("65536 * 32768"),
+        Err(Error::Overflow(Pos { line: 1, col: 7 }))
+    );
+    let src = "let min := 0 - 2147483647 - 1 in\n-min";
+    assert_eq!(run(src), Err(Error::Overflow(Pos { line: 2, col: 1 })));
+    assert_eq!(run("2147483646 + 1"), Ok(String::from("Int(2147483647)")));
+}
+
+#[test]
+fn div_by_zero_in_callee() {
+    let src = "let f x := 10 / x in\nf 5 + f 0";
+    assert_eq!(run(src), Err(Error::DivByZero(Pos { line: 1, col: 15 })));
+}
+
+#[test]
+fn modulo() {
+    assert_eq!(run("17 % 5"), Ok(String::from("Int(2)")));
+    assert_eq!(run("(0 - 17) % 5"), Ok(String::from("Int(-2)")));
+    assert_eq!(run("2 + 17 % 5 * 3"), Ok(String::from("Int(8)")));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(run("1.5 * 2.0 - 0.5"), Ok(String::from("Float(2.5)")));
+    assert_eq!(run("7.5 % 2.0"), Ok(String::from("Float(1.5)")));
+    assert_eq!(run("1.0 / 0.0"), Ok(String::from("Float(inf)")));
+    assert_eq!(run("2.5e3 <= 2500.0"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("int_of_float (0.0 - 3.7)"), Ok(String::from("Int(-3)")));
+    assert_eq!(run("float_of_int 3 / 2.0"), Ok(String::from("Float(1.5)")));
+}
+
+#[test]
+fn float_round_trip() {
+    for src in ["0.1 + 0.2", "1.0e300 * 10.0", "1.0 / 3.0e7", "2.0 * 3.0"] {
+        let printed = run(src).unwrap();
+        let lit = &printed["Float(".len()..printed.len() - 1];
+        assert_eq!(run(lit), Ok(printed.clone()));
+    }
+}
+
+#[test]
+fn short_circuit() {
+    let src = "let x := 0 in x != 0 && 10 / x > 1";
+    assert_eq!(run(src), Ok(String::from("Bool(false)")));
+    let src = "let x := 0 in x == 0 || 10 / x > 1";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+    let src = "let rec loop x := loop x in false && loop 0";
+    assert_eq!(run(src), Ok(String::from("Bool(false)")));
+    let src = "let rec loop x := loop x in true || loop 0";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+    assert_eq!(run("true && 1 < 2"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("false || 1 > 2"), Ok(String::from("Bool(false)")));
+}
+
+#[test]
+fn equality() {
+    assert_eq!(run("true == false"), Ok(String::from("Bool(false)")));
+    assert_eq!(run("true != false"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("(1 < 2) == (3 < 4)"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("false < true"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("0.5 == 0.5"), Ok(String::from("Bool(true)")));
+    let src = "let nan := 0.0 / 0.0 in nan != nan && (! (nan <= nan))";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+}
+
+#[test]
+fn equality_errors() {
+    let pos = Pos { line: 1, col: 3 };
+    assert_eq!(run("1 == true"), Err(Error::Incomparable(pos)));
+    assert_eq!(run("1 <= 1.0"), Err(Error::Incomparable(pos)));
+    let src = "let f x := x in\nf == f";
+    let pos = Pos { line: 2, col: 3 };
+    assert_eq!(run(src), Err(Error::FunctionalValue(pos)));
+}
+
+#[test]
+fn references() {
+    let src = "
+let counter := ref 0 in
+let tick u := counter := !counter + 1 in
+let rec loop n := if n <= 0 then !counter else let t := tick 0 in loop (n - 1) in
+loop 10";
+    assert_eq!(run(src), Ok(String::from("Int(10)")));
+    let src = "let r := ref 1 in let s := r in let u := s := 2 in !r";
+    assert_eq!(run(src), Ok(String::from("Int(2)")));
+    let src = "let r := ref 1 in let f x := x + 1 in f (!r) * 10";
+    assert_eq!(run(src), Ok(String::from("Int(20)")));
+    assert_eq!(run("ref 1 == ref 1"), Ok(String::from("Bool(true)")));
+    assert_eq!(run("!true != false"), Ok(String::from("Bool(false)")));
+    // ! is negation, except right before a variable or a parenthesis
+    let src = "let b := ref false in ! !b";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+}
+
+#[test]
+fn cyclic_references() {
+    let src = "let r := ref 0 in let u := r := r in r == r";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+    let src = "let r := ref 0 in let s := ref 1 in let u := r := s in let v := s := r in r == s";
+    assert_eq!(run(src), Ok(String::from("Bool(true)")));
+}
+
+#[test]
+fn exceptions() {
+    assert_eq!(
+        run("try 1 + raise 2 with e => e * 10"),
+        Ok(String::from("Int(20)"))
+    );
+    assert_eq!(
+        run("try 1 + 2 with e => e * 10"),
+        Ok(String::from("Int(3)"))
+    );
+    assert_eq!(run("try 1 / 0 with e => e"), Ok(String::from("Int(1)")));
+    let src = "try (try raise 1 with e => raise (e + 1)) with e => e";
+    assert_eq!(run(src), Ok(String::from("Int(2)")));
+    let src = "let f x := if x == 0 then raise true else x in\nf 0";
+    let pos = Pos { line: 1, col: 27 };
+    assert_eq!(
+        run(src),
+        Err(Error::Uncaught(String::from("Bool(true)"), pos))
+    );
+}
+
+#[test]
+fn exceptions_restore_env() {
+    let src = "
+let rec f n :=
+  let y := n in
+  let r := try (if n == 0 then raise 0 else f (n - 1)) with e => 100 in
+  y + n * 10 + r
+in f 1";
+    assert_eq!(run(src), Ok(String::from("Int(111)")));
+}
+
+#[test]
+fn effects_state() {
+    let src = "
+effect get in
+effect put in
+let rec count n :=
+  if n <= 0 then
+    perform get 0
+  else
+    let s := perform get 0 in
+    let u := perform put (s + n) in
+    count (n - 1)
+in
+(handle count 10 with
+| get u k => (fun s => k s s)
+| put s k => (fun t => k s s)
+| return x => (fun s => x)) 0";
+    assert_eq!(run(src), Ok(String::from("Int(55)")));
+}
+
+#[test]
+fn effects_generator() {
+    let prelude = "
+effect yield in
+let rec gen n :=
+  if n > 5 then 0 else let u := perform yield n in gen (n + 1)
+in ";
+    let src = format!("{}handle gen 1 with | yield x k => x + k 0", prelude);
+    assert_eq!(run(&src), Ok(String::from("Int(15)")));
+    let src = format!(
+        "{}handle gen 1 with | yield x k => if x == 3 then 100 else k 0",
+        prelude
+    );
+    assert_eq!(run(&src), Ok(String::from("Int(100)")));
+    let src = format!(
+        "{}handle gen 1 with | yield x k => k 0 | return x => x - 1",
+        prelude
+    );
+    assert_eq!(run(&src), Ok(String::from("Int(-1)")));
+}
+
+#[test]
+fn effects_escaping_continuation() {
+    let src = "effect e in let k := handle perform e 1 + 10 with | e x k => k in k 5";
+    assert_eq!(run(src), Ok(String::from("Int(15)")));
+    let src = "effect e in try (handle raise 3 with | e x k => 0) with x => x + 1";
+    assert_eq!(run(src), Ok(String::from("Int(4)")));
+}
+
+#[test]
+fn effects_errors() {
+    let pos = Pos { line: 1, col: 13 };
+    let src = "effect e in perform e 1";
+    assert_eq!(run(src), Err(Error::Unhandled(String::from("e"), pos)));
+    let src = "effect e in handle perform e 1 with | e x k => k 1 + k 2";
+    assert_eq!(run(src), Err(Error::Resumed(Pos { line: 1, col: 20 })));
+}
+
+#[test]
+fn effects_namespace() {
+    // an effect operation neither shadows nor is shadowed by a variable
+    let src = "effect e in let e := 5 in handle perform e e with | e x k => k (x + 1)";
+    assert_eq!(run(src), Ok(String::from("Int(6)")));
+    let src = "let e := 1 in effect e in e + 1";
+    assert_eq!(run(src), Ok(String::from("Int(2)")));
+}
+
+#[test]
+fn callcc_escape() {
+    assert_eq!(
+        run("1 + callcc (fun k => 10 + k 5)"),
+        Ok(String::from("Int(6)"))
+    );
+    let src = "let f x := callcc (fun k => if x == 0 then k 0 else 100 / x) in f 0 + f 5";
+    assert_eq!(run(src), Ok(String::from("Int(20)")));
+    // k is called after its callcc has returned and goes back to the let
+    let src = "
+let saved := ref (fun x => x) in
+let count := ref 0 in
+let r := callcc (fun k => let u := saved := k in 0) in
+let u := count := !count + 1 in
+if !count < 3 then !saved (r + 10) else r";
+    assert_eq!(run(src), Ok(String::from("Int(20)")));
+}
+
+#[test]
+fn lexical_scoping() {
+    let src = std::fs::read_to_string("examples/test6.txt").unwrap();
+    assert_eq!(run(&src), Ok(String::from("Int(10)")));
+    let src = "let x := 1 in let f := (let x := 2 in fun u => x) in f 0 + x";
+    assert_eq!(run(src), Ok(String::from("Int(3)")));
+    let src = "let f x := (let y := x in y) + (try raise x with y => y) in f 1 + f 2";
+    assert_eq!(run(src), Ok(String::from("Int(6)")));
+}
+
+#[test]
+fn closures_over_recursive_calls() {
+    // every call of mk captures its own n
+    let src = "let rec mk n :=
+  if n == 0 then fun u => u
+  else let g := mk (n - 1) in fun u => n + g u
+in mk 4 0";
+    assert_eq!(run(src), Ok(String::from("Int(10)")));
+    // h is built before and called after the recursive call
+    let src = "let rec f n :=
+  if n == 0 then fun u => u
+  else let h := fun u => n * 10 + u in let k := f (n - 1) in fun u => h (k u)
+in f 3 0";
+    assert_eq!(run(src), Ok(String::from("Int(60)")));
+}
+
+#[test]
+fn reentrant_closures() {
+    // f calls itself through a reference while its own call is in progress
+    let src = "let r := ref (fun x => 0) in
+let f x := if x == 0 then 0 else let y := x in let z := !r (x - 1) in y + z in
+let u := r := f in f 3";
+    assert_eq!(run(src), Ok(String::from("Int(6)")));
+    let src = "let twice g x := g (g x) in
+let rec f n := if n <= 0 then 1 else twice (fun m => m + f (n - 1)) n in f 3";
+    assert_eq!(run(src), Ok(String::from("Int(19)")));
+}
+
+#[test]
+fn calls_leave_closures_alone() {
+    let src = "let f x := let y := x + 1 in y in let u := f 1 in f";
+    let tm = common::source(src);
+    match eval(&RefCell::new(HashMap::default()), &tm) {
+        Ok(Value::Clo(clo)) => assert!(clo.env.is_empty()),
+        _ => panic!(),
+    }
+}
@@ -0,0 +1,224 @@
+// random ast0 programs of bounded size, every variable is bound and typed
+// programs never get stuck or diverge
+use proptest::{prelude::*, sample::select};
+use rust_test::{ast0::*, error::Pos};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    // int -> int
+    Fun,
+}
+
+type Scope = Rc<Vec<(String, Ty)>>;
+
+// a recursive function and its parameter, calls to it decrease the argument
+type Rec = Option<(String, String)>;
+
+const POS: Pos = Pos { line: 1, col: 1 };
+
+// few names so that binders often shadow each other
+const NAMES: [&str; 3] = ["a", "b", "c"];
+
+pub fn program(typed: bool) -> BoxedStrategy<Box<Term>> {
+    select(vec![Ty::Int, Ty::Float, Ty::Bool])
+        .prop_flat_map(move |ty| term(ty, Rc::new(vec![]), None, 5, typed))
+        .boxed()
+}
+
+// subterm strategies are only built once their alternative is picked
+fn term(ty: Ty, scope: Scope, rec: Rec, depth: u32, typed: bool) -> BoxedStrategy<Box<Term>> {
+    if typed {
+        return Just(())
+            .prop_flat_map(move |_| node(ty, scope.clone(), rec.clone(), depth, true))
+            .boxed();
+    }
+    select(vec![Ty::Int, Ty::Float, Ty::Bool, Ty::Fun])
+        .prop_flat_map(move |ty| node(ty, scope.clone(), rec.clone(), depth, false))
+        .boxed()
+}
+
+fn extend(scope: &Scope, xs: &[(&str, Ty)]) -> Scope {
+    let mut scope = (**scope).clone();
+    scope.retain(|(y, _)| xs.iter().all(|(x, _)| x != y));
+    scope.extend(xs.iter().map(|(x, ty)| (x.to_string(), *ty)));
+    Rc::new(scope)
+}
+
+fn leaf(ty: Ty, scope: &Scope, rec: &Rec, typed: bool) -> BoxedStrategy<Box<Term>> {
+    let mut leaves = vec![match ty {
+        Ty::Int => prop_oneof![-3..10, any::<i32>()].prop_map(int).boxed(),
+        Ty::Float => prop_oneof![select(vec![0.0, -0.0, 0.5, 1.5, 1e300]), any::<f64>()]
+            .prop_map(float)
+            .boxed(),
+        Ty::Bool => any::<bool>().prop_map(bool).boxed(),
+        Ty::Fun => Just(fun(
+            String::new(),
+            String::from("a"),
+            var(String::from("a")),
+        ))
+        .boxed(),
+    }];
+    let xs: Vec<String> = scope
+        .iter()
+        .filter(|(_, t)| !typed || *t == ty)
+        .map(|(x, _)| x.clone())
+        .collect();
+    if !xs.is_empty() {
+        leaves.push(select(xs).prop_map(var).boxed());
+    }
+    if let (Ty::Int, Some((f, n))) = (ty, rec) {
+        let call = app(
+            var(f.clone()),
+            op2(Op2::Sub, var(n.clone()), int(1), POS),
+            POS,
+        );
+        leaves.push(Just(call).boxed());
+    }
+    select(leaves).prop_flat_map(|s| s).boxed()
+}
+
+fn node(ty: Ty, scope: Scope, rec: Rec, depth: u32, typed: bool) -> BoxedStrategy<Box<Term>> {
+    if depth == 0 {
+        return leaf(ty, &scope, &rec, typed);
+    }
+    let d = depth - 1;
+    let sub = {
+        let (scope, rec) = (scope.clone(), rec.clone());
+        move |ty| term(ty, scope.clone(), rec.clone(), d, typed)
+    };
+    let mut alts = vec![
+        leaf(ty, &scope, &rec, typed),
+        (
+            select(NAMES.to_vec()),
+            select(vec![Ty::Int, Ty::Float, Ty::Bool, Ty::Fun]),
+        )
+            .prop_flat_map({
+                let (scope, rec) = (scope.clone(), rec.clone());
+                move |(x, t)| {
+                    let body = extend(&scope, &[(x, t)]);
+                    (
+                        term(t, scope.clone(), rec.clone(), d, typed),
+                        term(ty, body, rec.clone(), d, typed),
+                    )
+                        .prop_map(move |(m, n)| letin(x.to_string(), m, n))
+                }
+            })
+            .boxed(),
+        (sub(Ty::Bool), sub(ty), sub(ty))
+            .prop_map(|(m, n1, n2)| ifte(m, n1, n2))
+            .boxed(),
+        (sub(ty), select(NAMES.to_vec()))
+            .prop_flat_map({
+                let (scope, rec) = (scope.clone(), rec.clone());
+                move |(m, x)| {
+                    term(ty, extend(&scope, &[(x, Ty::Int)]), rec.clone(), d, typed)
+                        .prop_map(move |n| trywith(m.clone(), x.to_string(), n))
+                }
+            })
+            .boxed(),
+        sub(Ty::Int).prop_map(|m| raise(m, POS)).boxed(),
+    ];
+    let arith = select(vec![Op2::Add, Op2::Sub, Op2::Mul, Op2::Div, Op2::Mod]);
+    let compare = select(vec![
+        Op2::Lte,
+        Op2::Gte,
+        Op2::Lt,
+        Op2::Gt,
+        Op2::Eq,
+        Op2::Neq,
+    ]);
+    match ty {
+        Ty::Int => {
+            alts.push(
+                (arith, sub(Ty::Int), sub(Ty::Int))
+                    .prop_map(|(op, m, n)| op2(op, m, n, POS))
+                    .boxed(),
+            );
+            alts.push(sub(Ty::Int).prop_map(|m| op1(Op1::Neg, m, POS)).boxed());
+            alts.push(
+                sub(Ty::Float)
+                    .prop_map(|m| op1(Op1::IntOfFloat, m, POS))
+                    .boxed(),
+            );
+            alts.push(
+                (sub(Ty::Fun), sub(Ty::Int))
+                    .prop_map(|(m, n)| app(m, n, POS))
+                    .boxed(),
+            );
+        }
+        Ty::Float => {
+            alts.push(
+                (arith, sub(Ty::Float), sub(Ty::Float))
+                    .prop_map(|(op, m, n)| op2(op, m, n, POS))
+                    .boxed(),
+            );
+            alts.push(sub(Ty::Float).prop_map(|m| op1(Op1::Neg, m, POS)).boxed());
+            alts.push(
+                sub(Ty::Int)
+                    .prop_map(|m| op1(Op1::FloatOfInt, m, POS))
+                    .boxed(),
+            );
+        }
+        Ty::Bool => {
+            alts.push(
+                (compare, select(vec![Ty::Int, Ty::Float, Ty::Bool]))
+                    .prop_flat_map({
+                        let sub = sub.clone();
+                        move |(op, t)| {
+                            (sub(t), sub(t)).prop_map(move |(m, n)| op2(op.clone(), m, n, POS))
+                        }
+                    })
+                    .boxed(),
+            );
+            alts.push(
+                (
+                    select(vec![Op2::And, Op2::Or]),
+                    sub(Ty::Bool),
+                    sub(Ty::Bool),
+                )
+                    .prop_map(|(op, m, n)| op2(op, m, n, POS))
+                    .boxed(),
+            );
+            alts.push(sub(Ty::Bool).prop_map(|m| op1(Op1::Not, m, POS)).boxed());
+        }
+        Ty::Fun => {
+            alts.push(
+                select(NAMES.to_vec())
+                    .prop_flat_map({
+                        let scope = scope.clone();
+                        move |x| {
+                            let body = extend(&scope, &[(x, Ty::Int)]);
+                            term(Ty::Int, body, None, d, typed)
+                                .prop_map(move |m| fun(String::new(), x.to_string(), m))
+                        }
+                    })
+                    .boxed(),
+            );
+            // f is only called on n - 1, so the guard bounds the recursion
+            let f = format!("f{}", depth);
+            let n = format!("n{}", depth);
+            let body = extend(&scope, &[(&n, Ty::Int)]);
+            let guard = op2(
+                Op2::Or,
+                op2(Op2::Lte, var(n.clone()), int(0), POS),
+                op2(Op2::Lt, int(4), var(n.clone()), POS),
+                POS,
+            );
+            alts.push(
+                (
+                    term(Ty::Int, body.clone(), None, d, typed),
+                    term(Ty::Int, body, Some((f.clone(), n.clone())), d, typed),
+                )
+                    .prop_map(move |(m1, m2)| {
+                        fun(f.clone(), n.clone(), ifte(guard.clone(), m1, m2))
+                    })
+                    .boxed(),
+            );
+        }
+    }
+    select(alts).prop_flat_map(|s| s).boxed()
+}
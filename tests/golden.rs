@@ -0,0 +1,190 @@
+// runs every example through each stage and backend and compares with
+// tests/golden/NAME.expected, `BLESS=1 cargo test --test golden` rewrites
+// the expected files
+use ahash::HashMap;
+use rust_test::{
+    ast1, ast2, ast3, ast5, ast6, cek,
+    error::{Error, Exn},
+    eval::{Value, eval},
+    lazy, opt,
+    parse::parse_prog,
+    smallstep, trans01, trans02, trans13, trans14, trans15, trans16,
+};
+use std::{
+    cell::RefCell,
+    fmt::{Debug, Display},
+    fs,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+const MAX_STEPS: usize = 10_000_000;
+const MAX_SMALL_STEPS: usize = 100_000;
+
+// the examples are sized as benchmarks, these are run on smaller inputs so
+// that every machine finishes, church numerals also grow into huge terms
+// under substitution and test2 by name grows exponentially with the depth
+// of the recursion
+const SMALLER: &[(&str, &str, &str)] = &[
+    ("test1", "fact ten", "fact three"),
+    ("test2", "mccarthy 23", "mccarthy 90"),
+    ("test4", "fibo 30", "fibo 15"),
+    ("test7", "fibo 30", "fibo 15"),
+];
+
+fn show<V: Debug>(result: Option<Result<V, Exn<V>>>) -> String {
+    match result {
+        Some(Ok(v)) => format!("{:?}", v),
+        Some(Err(e)) => format!("runtime error : {}", Error::from(e)),
+        None => String::from("stopped, step limit reached"),
+    }
+}
+
+fn translated<P: Display>(prog: Result<P, Error>) -> String {
+    match prog {
+        Ok(prog) => prog.to_string(),
+        Err(e) => format!("translation error : {}", e),
+    }
+}
+
+fn guard(f: impl FnOnce() -> String) -> Result<String, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|e| match e.downcast_ref::<String>() {
+        Some(msg) => msg.clone(),
+        None => e.downcast_ref::<&str>().unwrap_or(&"").to_string(),
+    })
+}
+
+fn machine<'a>(mut step: impl FnMut() -> Option<Result<Value<'a>, Exn<Value<'a>>>>) -> String {
+    show((0..MAX_STEPS).find_map(|_| step()))
+}
+
+fn reduce(tm: &ast1::Term) -> String {
+    let mut m = Box::new(tm.clone());
+    for _ in 0..MAX_SMALL_STEPS {
+        match smallstep::step(&m) {
+            Ok(Some(m1)) => m = m1,
+            Ok(None) => {
+                return match *m {
+                    ast1::Term::Raise(v, pos) => show(Some(Err(Exn::Raise(v, pos)))),
+                    m => show::<ast1::Term>(Some(Ok(m))),
+                };
+            }
+            Err(e) => return show::<ast1::Term>(Some(Err(e.into()))),
+        }
+    }
+    show::<ast1::Term>(None)
+}
+
+// the output of every stage, or the stages that panicked
+fn golden(src: &str) -> Result<String, Vec<String>> {
+    let tm0 = match parse_prog(src) {
+        Ok(tm) => tm,
+        Err(e) => return Ok(format!("== parse\n{}\n", e)),
+    };
+    let tm = trans01::trans(&mut trans01::Ctx::default(), &tm0);
+    let stages: [(&str, &dyn Fn() -> String); 17] = [
+        ("trans01", &|| tm.to_string()),
+        ("opt", &|| opt::optimize(&tm, 1).to_string()),
+        ("inline", &|| opt::optimize(&tm, 2).to_string()),
+        ("eval", &|| {
+            show(Some(eval(&RefCell::new(HashMap::default()), &tm)))
+        }),
+        ("cek", &|| {
+            let mut m = cek::Machine::new(Rc::new(HashMap::default()), &tm);
+            machine(|| m.step())
+        }),
+        ("need", &|| {
+            let mut m = lazy::Machine::new(Rc::new(HashMap::default()), &tm, true);
+            machine(|| m.step())
+        }),
+        ("name", &|| {
+            let mut m = lazy::Machine::new(Rc::new(HashMap::default()), &tm, false);
+            machine(|| m.step())
+        }),
+        ("smallstep", &|| reduce(&tm)),
+        ("ast2", &|| {
+            let tm = trans02::trans(Rc::new(HashMap::default()), &tm0).map_err(Exn::Error);
+            show(Some(tm.and_then(ast2::reduce)))
+        }),
+        ("trans13", &|| translated(trans13::trans(&tm))),
+        ("ast3", &|| {
+            let prog = trans13::trans(&tm).map_err(Exn::Error);
+            show(Some(prog.and_then(|prog| ast3::run(&prog))))
+        }),
+        ("trans14", &|| trans14::trans(&tm).to_string()),
+        ("ast4", &|| {
+            let tm = trans14::untrans(&trans14::trans(&tm));
+            show(Some(eval(&RefCell::new(HashMap::default()), &tm)))
+        }),
+        ("trans15", &|| translated(trans15::trans(&tm))),
+        ("ast5", &|| match trans15::trans(&tm) {
+            Ok(tm) => show(Some(ast5::run(&tm))),
+            Err(e) => show::<ast5::Value>(Some(Err(e.into()))),
+        }),
+        ("trans16", &|| translated(trans16::trans(&tm))),
+        ("ast6", &|| {
+            let prog = trans16::trans(&tm).map_err(Exn::Error);
+            show(Some(prog.and_then(|prog| ast6::run(&prog))))
+        }),
+    ];
+    let mut out = String::new();
+    let mut panics = vec![];
+    for (stage, run) in stages {
+        let result = match guard(run) {
+            Ok(result) => result,
+            Err(msg) => {
+                panics.push(format!("{} panicked: {}", stage, msg));
+                continue;
+            }
+        };
+        out += &format!("== {}\n{}\n", stage, result);
+    }
+    match panics.is_empty() {
+        true => Ok(out),
+        false => Err(panics),
+    }
+}
+
+#[test]
+fn examples() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let mut paths: Vec<_> = fs::read_dir("examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    let mut failed = vec![];
+    for path in paths {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let mut src = fs::read_to_string(&path).unwrap();
+        for (_, from, to) in SMALLER.iter().filter(|(n, ..)| *n == name) {
+            assert!(src.contains(from), "{} no longer contains {}", name, from);
+            src = src.replace(from, to);
+        }
+        let actual = match golden(&src) {
+            Ok(actual) => actual,
+            Err(panics) => {
+                failed.push((name, panics.join("\n")));
+                continue;
+            }
+        };
+        let expected = format!("tests/golden/{}.expected", name);
+        if bless {
+            fs::write(&expected, &actual).unwrap();
+        } else if fs::read_to_string(&expected).ok().as_deref() != Some(actual.as_str()) {
+            failed.push((name, actual));
+        }
+    }
+    panic::set_hook(hook);
+    for (name, actual) in &failed {
+        eprintln!("---- {}\n{}", name, actual);
+    }
+    let names: Vec<_> = failed.iter().map(|(name, _)| name.as_str()).collect();
+    assert!(
+        names.is_empty(),
+        "{:?} panicked or differ from tests/golden, rerun with BLESS=1 to accept",
+        names
+    );
+}
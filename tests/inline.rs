@@ -0,0 +1,123 @@
+mod common;
+mod generator;
+
+use ahash::{HashMap, HashSet};
+use proptest::prelude::*;
+use rust_test::{ast1::Term, cek, inline, names::Name, opt};
+use std::rc::Rc;
+
+fn optimize(src: &str) -> String {
+    opt::optimize(&common::source(src), 2).to_string()
+}
+
+// no name is bound twice, which the renaming of copied bodies keeps true
+fn distinct(m0: &Term, seen: &mut HashSet<Name>) -> bool {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) | Var(_) => true,
+        Op1(_, m, _) | Raise(m, _) | Perform(_, m, _) | CallCC(m, _) => distinct(m, seen),
+        Op2(_, m, n, _) | App(m, n, _) => distinct(m, seen) && distinct(n, seen),
+        Fun(f, x, m) => {
+            (f.to_string().is_empty() || seen.insert(f.clone()))
+                && seen.insert(x.clone())
+                && distinct(m, seen)
+        }
+        LetIn(x, m, n) | Try(m, x, n) => {
+            distinct(m, seen) && seen.insert(x.clone()) && distinct(n, seen)
+        }
+        Ifte(m, n1, n2) => distinct(m, seen) && distinct(n1, seen) && distinct(n2, seen),
+        Effect(op, m) => seen.insert(op.clone()) && distinct(m, seen),
+        Handle(m, x, n, cases) => {
+            distinct(m, seen)
+                && seen.insert(x.clone())
+                && distinct(n, seen)
+                && cases.iter().all(|(_, y, k, m)| {
+                    seen.insert(y.clone()) && seen.insert(k.clone()) && distinct(m, seen)
+                })
+        }
+    }
+}
+
+fn betas(tm: &Term) -> (usize, String) {
+    let mut machine = cek::Machine::new(Rc::new(HashMap::default()), tm);
+    let result = (0..100_000_000).find_map(|_| machine.step()).unwrap();
+    (machine.betas, format!("{:?}", result))
+}
+
+#[test]
+fn curried_functions() {
+    assert_eq!(optimize("let add x y := x + y in add 1 2"), "3");
+    assert_eq!(
+        optimize("let add x y z := x + y * z in fun a => add a (a + 1) 2"),
+        "fun a => let y' := a + 1 in a + y' * 2"
+    );
+    // a partial application is a function as well
+    assert_eq!(
+        optimize("let add x y := x + y in let inc := add 1 in fun a => inc (inc a)"),
+        "fun a => let y'' := 1 + a in 1 + y''"
+    );
+}
+
+#[test]
+fn arguments_are_evaluated_once() {
+    assert_eq!(
+        optimize("let r := ref 0 in let f x := x + x in f (r := !r + 1)"),
+        "let r := ref 0 in let x' := r := !r + 1 in x' + x'"
+    );
+    assert_eq!(
+        optimize("let f x y := y in fun a => f (1 / a) 2"),
+        "fun a => let x' := 1 / a in 2"
+    );
+}
+
+#[test]
+fn copies_are_renamed() {
+    let tm = opt::optimize(
+        &common::source("let f x := let y := x * 2 in y + y in fun a => f a + f (a + 1)"),
+        2,
+    );
+    assert_eq!(
+        tm.to_string(),
+        "fun a => (let y' := a * 2 in y' + y') + (let x' := a + 1 in let y' := x' * 2 in y' + y')"
+    );
+    assert!(distinct(&tm, &mut HashSet::default()));
+}
+
+#[test]
+fn what_is_not_inlined() {
+    let src = "let rec f n := if n == 0 then 0 else f (n - 1) in f 3";
+    assert_eq!(
+        optimize(src),
+        "let rec f n := if n == 0 then 0 else f (n - 1) in f 3"
+    );
+    let body = (0..40)
+        .map(|i| format!("x * {}", i))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let src = format!("let big x := {} in big 1", body);
+    assert_eq!(
+        optimize(&src),
+        opt::optimize(&common::source(&src), 1).to_string()
+    );
+    // self application stops when the inliner runs out of fuel
+    let tm = inline::inline(&common::source("(fun x => x x) (fun x => x x)"));
+    assert!(inline::size(&tm) < 100);
+}
+
+#[test]
+fn church_numerals() {
+    let tm = common::example("test1");
+    let (before, expected) = betas(&tm);
+    let (after, result) = betas(&opt::optimize(&tm, 2));
+    assert_eq!(result, expected);
+    assert!(after * 2 < before, "{} betas, {} before", after, before);
+}
+
+proptest! {
+    #[test]
+    fn binders_stay_distinct(m in generator::program(false)) {
+        let tm = common::resolve(&m);
+        prop_assume!(distinct(&tm, &mut HashSet::default()));
+        prop_assert!(distinct(&inline::inline(&tm), &mut HashSet::default()));
+    }
+}
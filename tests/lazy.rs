@@ -0,0 +1,133 @@
+mod common;
+
+use ahash::HashMap;
+use rust_test::{
+    cek,
+    error::{Error, Pos},
+    eval::eval,
+    lazy,
+};
+use std::{cell::RefCell, fs, rc::Rc};
+
+fn run(src: &str) -> Result<String, Error> {
+    common::outcome(lazy::eval(
+        &Rc::new(HashMap::default()),
+        &common::source(src),
+    ))
+}
+
+#[test]
+fn unused_arguments() {
+    let src = "let rec loop x := loop x in let x := loop 0 in 5";
+    assert_eq!(run(src), Ok(String::from("Int(5)")));
+    assert_eq!(run("(fun x => 1) (1 / 0)"), Ok(String::from("Int(1)")));
+    let src = "let k x y := x in k 3 (raise 4)";
+    assert_eq!(run(src), Ok(String::from("Int(3)")));
+}
+
+#[test]
+fn infinite_structures() {
+    let src = "
+let rec nats n f := f n (nats (n + 1)) in
+let rec nth s i := s (fun x rest => if i == 0 then x else nth rest (i - 1)) in
+nth (nats 0) 42";
+    assert_eq!(run(src), Ok(String::from("Int(42)")));
+}
+
+#[test]
+fn thunks_are_forced_once() {
+    let src = "
+let c := ref 0 in
+let x := (c := !c + 1) * 0 + 10 in
+let f y := y + y in
+f x + !c";
+    assert_eq!(run(src), Ok(String::from("Int(21)")));
+}
+
+#[test]
+fn deep_thunk_chain() {
+    let src = "
+let rec count n acc := if n == 0 then acc else count (n - 1) (acc + 1) in
+count 100000 0";
+    assert_eq!(run(src), Ok(String::from("Int(100000)")));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        run("let x := 1 / 0 in x + 1"),
+        Err(Error::DivByZero(Pos { line: 1, col: 12 }))
+    );
+    assert_eq!(
+        run("try 1 + raise 2 with x => x * 10"),
+        Ok(String::from("Int(20)"))
+    );
+    let unsupported = Err(Error::Unsupported(String::from(
+        "callcc under lazy evaluation",
+    )));
+    assert_eq!(run("1 + callcc (fun k => k 1)"), unsupported);
+    assert_eq!(run("try callcc (fun k => k 1) with e => 0"), unsupported);
+    assert_eq!(
+        run("effect e in handle perform e 1 with | e x k => k x"),
+        Err(Error::Unsupported(String::from(
+            "effect handlers under lazy evaluation"
+        )))
+    );
+}
+
+#[test]
+fn call_by_name_repeats_effects() {
+    let src = "
+let c := ref 0 in
+let x := (c := !c + 1) * 0 + 10 in
+let f y := y + y in
+f x + !c";
+    let tm = common::source(src);
+    let v = lazy::eval(&Rc::new(HashMap::default()), &tm).unwrap();
+    assert_eq!(format!("{:?}", v), "Int(21)");
+    // `ref 0` itself is evaluated again on every use of c
+    let v = lazy::eval_by_name(&Rc::new(HashMap::default()), &tm).unwrap();
+    assert_eq!(format!("{:?}", v), "Int(20)");
+}
+
+#[test]
+fn strategy_counts() {
+    let src = "let sq x := x * x in let k x y := x in k 1 (sq (sq 3))";
+    let tm = common::source(src);
+    let mut value = cek::Machine::new(Rc::new(HashMap::default()), &tm);
+    assert_eq!(format!("{:?}", value.run()), "Ok(Int(1))");
+    assert_eq!((value.betas, value.ops), (4, 2));
+    for memo in [true, false] {
+        let mut machine = lazy::Machine::new(Rc::new(HashMap::default()), &tm, memo);
+        assert_eq!(format!("{:?}", machine.run()), "Ok(Int(1))");
+        assert_eq!((machine.betas, machine.ops), (2, 0));
+    }
+    let src = "let sq x := x * x in sq (sq 3)";
+    let tm = common::source(src);
+    let mut need = lazy::Machine::new(Rc::new(HashMap::default()), &tm, true);
+    assert_eq!(format!("{:?}", need.run()), "Ok(Int(81))");
+    assert_eq!((need.betas, need.ops), (2, 2));
+    let mut name = lazy::Machine::new(Rc::new(HashMap::default()), &tm, false);
+    assert_eq!(format!("{:?}", name.run()), "Ok(Int(81))");
+    assert_eq!((name.betas, name.ops), (3, 3));
+}
+
+#[test]
+fn examples_agree_with_eval() {
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        // test1 builds a chain of 3628800 thunks, test7's memo table is never
+        // forced, test8 uses effect handlers
+        if ["test1.txt", "test7.txt", "test8.txt"]
+            .iter()
+            .any(|f| path.ends_with(f))
+        {
+            continue;
+        }
+        let src = fs::read_to_string(path).unwrap();
+        let tm = common::source(&src);
+        let expected = common::outcome(eval(&RefCell::new(HashMap::default()), &tm));
+        let actual = common::outcome(lazy::eval(&Rc::new(HashMap::default()), &tm));
+        assert_eq!(expected.ok(), actual.ok());
+    }
+}
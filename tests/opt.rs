@@ -0,0 +1,97 @@
+mod common;
+mod generator;
+
+use proptest::prelude::*;
+use rust_test::{inline::size, opt};
+
+fn optimize(src: &str) -> String {
+    opt::optimize(&common::source(src), 1).to_string()
+}
+
+#[test]
+fn constant_folding() {
+    assert_eq!(optimize("2 + 3 * 4"), "14");
+    assert_eq!(optimize("1.5 * 2.0 <= 3.0 && (!false)"), "true");
+    assert_eq!(optimize("int_of_float (float_of_int 7 / 2.0)"), "3");
+    assert_eq!(optimize("fun x => x + (10 - 4)"), "fun x => x + 6");
+    // errors are left to happen at run time
+    assert_eq!(optimize("1 / (2 - 2)"), "1 / 0");
+    assert_eq!(optimize("1 / 0 == 2"), "1 / 0 == 2");
+    assert_eq!(optimize("2147483647 + 1"), "2147483647 + 1");
+}
+
+#[test]
+fn algebraic_simplification() {
+    assert_eq!(optimize("fun x => x * 2 + 0"), "fun x => x * 2");
+    assert_eq!(optimize("fun x => 0 + (x - 1) * 1 - 0"), "fun x => x - 1");
+    assert_eq!(optimize("fun x => ! (! (x < 1))"), "fun x => x < 1");
+    assert_eq!(
+        optimize("fun x => true && (x == 1 || false)"),
+        "fun x => x == 1"
+    );
+    // x may be of another type, then the operation fails
+    assert_eq!(optimize("fun x => x + 0"), "fun x => x + 0");
+    assert_eq!(optimize("fun b => ! (! b)"), "fun b => ! (! b)");
+    assert_eq!(optimize("fun b => b && true"), "fun b => b && true");
+    assert_eq!(
+        optimize("fun x => if 1 < 2 then x else x / 0"),
+        "fun x => x"
+    );
+    assert_eq!(
+        optimize("fun x => if !true then x / 0 else x"),
+        "fun x => x"
+    );
+    assert_eq!(optimize("fun x => false && x / 0 == 1"), "fun x => false");
+}
+
+#[test]
+fn unused_bindings() {
+    assert_eq!(optimize("let x := 3 in let f y := y in 4"), "4");
+    assert_eq!(
+        optimize("let x := 1 in let y := (2 < 3) && (x == 1) in x"),
+        "let x := 1 in let y := x == 1 in x"
+    );
+    assert_eq!(
+        optimize("let x := 1 in let y := fun z => x + z in x"),
+        "let x := 1 in x"
+    );
+    // evaluating these can fail or change the store
+    assert_eq!(optimize("let x := 1 / 0 in 4"), "let x := 1 / 0 in 4");
+    assert_eq!(
+        optimize("let x := 1.5 in let y := x + 1 in 4"),
+        "let x := 1.5 in let y := x + 1 in 4"
+    );
+    assert_eq!(
+        optimize("fun b => let y := if b then 1 else 2 in 4"),
+        "fun b => let y := if b then 1 else 2 in 4"
+    );
+    assert_eq!(
+        optimize("let r := ref 0 in let u := r := 1 in !r"),
+        "let r := ref 0 in let u := r := 1 in !r"
+    );
+    assert_eq!(
+        optimize("let f x := x in let y := f 1 in 2"),
+        "let f := fun x => x in let y := f 1 in 2"
+    );
+}
+
+#[test]
+fn identities_keep_failures() {
+    // 1.5 + 0 fails unoptimized, so it has to fail optimized too
+    for src in ["1.5 + 0", "0 + 1.5", "1 && true", "! (! 1.5)"] {
+        let tm = common::source(src);
+        assert_eq!(
+            opt::optimize(&tm, 2).to_string(),
+            opt::optimize(&tm, 0).to_string()
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn never_grows(m in generator::program(false)) {
+        let tm = common::resolve(&m);
+        prop_assert!(size(&opt::optimize(&tm, 1)) <= size(&tm));
+        prop_assert_eq!(opt::optimize(&tm, 0).to_string(), tm.to_string());
+    }
+}
@@ -0,0 +1,278 @@
+mod generator;
+
+use ahash::HashMap;
+use proptest::{prelude::*, sample::select};
+use rust_test::{
+    ast0::{self, *},
+    ast1::{self, alpha_eq},
+    names::Name,
+    parse::parse_prog,
+    trans01::{self, Ctx},
+};
+
+// the term m's printed form parses to, the grammar has no negative
+// literals and named functions only appear in let rec
+fn syntactic(m0: &Term) -> Box<Term> {
+    use Term::*;
+    let pos = rust_test::error::Pos { line: 1, col: 1 };
+    match m0 {
+        Int(i32::MIN) => op2(
+            ast0::Op2::Sub,
+            op1(ast0::Op1::Neg, int(i32::MAX), pos),
+            int(1),
+            pos,
+        ),
+        Int(i) if *i < 0 => op1(ast0::Op1::Neg, int(-i), pos),
+        Float(x) if x.is_nan() => op2(ast0::Op2::Div, float(0.0), float(0.0), pos),
+        Float(x) if x.is_sign_negative() => op1(ast0::Op1::Neg, float(-x), pos),
+        Int(_) | Float(_) | Bool(_) | Var(_) => Box::new(m0.clone()),
+        Op1(op, m, pos) => op1(op.clone(), syntactic(m), *pos),
+        Op2(op, m, n, pos) => op2(op.clone(), syntactic(m), syntactic(n), *pos),
+        Fun(f, x, m) if f.is_empty() => fun(f.clone(), x.clone(), syntactic(m)),
+        Fun(f, x, m) => letin(
+            f.clone(),
+            fun(f.clone(), x.clone(), syntactic(m)),
+            var(f.clone()),
+        ),
+        App(m, n, pos) => app(syntactic(m), syntactic(n), *pos),
+        LetIn(x, m, n) => match m.as_ref() {
+            Fun(f, y, m) if f == x => letin(
+                x.clone(),
+                fun(f.clone(), y.clone(), syntactic(m)),
+                syntactic(n),
+            ),
+            _ => letin(x.clone(), syntactic(m), syntactic(n)),
+        },
+        Ifte(m, n1, n2) => ifte(syntactic(m), syntactic(n1), syntactic(n2)),
+        Raise(m, pos) => raise(syntactic(m), *pos),
+        Try(m, x, n) => trywith(syntactic(m), x.clone(), syntactic(n)),
+        _ => panic!("syntactic({:?})", m0),
+    }
+}
+
+fn bind(x: &str, names: &mut HashMap<String, Name>) {
+    names
+        .entry(x.to_string())
+        .or_insert_with(|| Name::new(x.to_string()));
+}
+
+// every identifier of m, so that free variables translate to the same names
+fn names(m0: &Term, ctx: &mut Ctx) {
+    use Term::*;
+    match m0 {
+        Int(_) | Float(_) | Bool(_) => (),
+        Var(x) => bind(x, &mut ctx.vars),
+        Perform(op, m, _) => {
+            bind(op, &mut ctx.ops);
+            names(m, ctx)
+        }
+        Handle(m, _, n, cases) => {
+            for (op, _, _, n) in cases {
+                bind(op, &mut ctx.ops);
+                names(n, ctx)
+            }
+            names(m, ctx);
+            names(n, ctx)
+        }
+        Op1(_, m, _) | Fun(_, _, m) | Raise(m, _) | Effect(_, m) | CallCC(m, _) => names(m, ctx),
+        Op2(_, m, n, _) | App(m, n, _) | LetIn(_, m, n) | Try(m, _, n) => {
+            names(m, ctx);
+            names(n, ctx)
+        }
+        Ifte(m, n1, n2) => {
+            names(m, ctx);
+            names(n1, ctx);
+            names(n2, ctx)
+        }
+    }
+}
+
+fn trans(m: &Term, ctx: &Ctx) -> Box<ast1::Term> {
+    trans01::trans(&mut ctx.clone(), m)
+}
+
+// parse, print, parse again and check both parses agree
+fn round_trip(src: &str) -> Result<(), TestCaseError> {
+    let Ok(m) = parse_prog(src) else {
+        return Ok(());
+    };
+    let mut ctx = Ctx::default();
+    names(&m, &mut ctx);
+    let printed = trans(&m, &ctx).to_string();
+    let n = parse_prog(&printed);
+    prop_assert!(n.is_ok(), "{} printed as {}", src, printed);
+    let n = trans(&n.unwrap(), &ctx);
+    prop_assert!(
+        alpha_eq(&trans(&m, &ctx), &n),
+        "{} printed as {}",
+        src,
+        printed
+    );
+    prop_assert_eq!(printed, n.to_string());
+    Ok(())
+}
+
+const TOKENS: [&str; 48] = [
+    "let",
+    "rec",
+    "in",
+    "fun",
+    "=>",
+    ":=",
+    "if",
+    "then",
+    "else",
+    "try",
+    "with",
+    "raise",
+    "effect",
+    "perform",
+    "handle",
+    "|",
+    "return",
+    "callcc",
+    "ref",
+    "!",
+    "!x",
+    "int_of_float",
+    "float_of_int",
+    "(",
+    ")",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "<=",
+    ">=",
+    "<",
+    "==",
+    "!=",
+    "&&",
+    "||",
+    "x",
+    "f",
+    "y",
+    "0",
+    "1",
+    "2147483648",
+    "1.5",
+    "1e999",
+    "true",
+    "false",
+    "(-1)",
+];
+
+fn tokens() -> impl Strategy<Value = String> {
+    prop::collection::vec(select(TOKENS.to_vec()), 0..24).prop_map(|ts| ts.join(" "))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1000))]
+
+    #[test]
+    fn printed_programs_parse_back(m in generator::program(false)) {
+        let ctx = Ctx::default();
+        let printed = trans(&m, &ctx).to_string();
+        let n = parse_prog(&printed);
+        prop_assert!(n.is_ok(), "cannot parse {}", printed);
+        prop_assert!(alpha_eq(&trans(&syntactic(&m), &ctx), &trans(&n.unwrap(), &ctx)), "{}", printed);
+        round_trip(&printed)?;
+    }
+
+    #[test]
+    fn parse_never_panics(src in "\\PC{0,40}") {
+        let _ = parse_prog(&src);
+    }
+
+    #[test]
+    fn token_soup_round_trips(src in tokens()) {
+        round_trip(&src)?;
+    }
+}
+
+fn parses_as(src: &str, expected: &str) {
+    let m = parse_prog(src).unwrap();
+    let mut ctx = Ctx::default();
+    names(&m, &mut ctx);
+    assert_eq!(trans(&m, &ctx).to_string(), expected);
+}
+
+#[test]
+fn prefix_operators() {
+    // prefix operators bind looser than application, and only start a term
+    parses_as("- f x", "-(f x)");
+    parses_as("f -1", "f - 1");
+    parses_as("f (-1)", "f (-1)");
+    parses_as("-1 - 2", "(-1) - 2");
+    parses_as("! f x && y", "(! (f x)) && y");
+    assert!(parse_prog("1 + -2").is_err());
+    assert!(parse_prog("x && ! y").is_err());
+}
+
+#[test]
+fn deref() {
+    // `!` right before a variable or a parenthesis reads a reference
+    parses_as("!r", "!r");
+    parses_as("!(f x)", "!(f x)");
+    parses_as("!f x && y", "!f x && y");
+    parses_as("x && !y", "x && !y");
+    parses_as("! !r", "! !r");
+    parses_as("!r != 1", "!r != 1");
+    // and negates anything else
+    parses_as("!true", "! true");
+    parses_as("! b && c", "(! b) && c");
+    parses_as("! (b && c)", "! (b && c)");
+    let m = parse_prog("!true").unwrap();
+    assert!(matches!(*m, ast0::Term::Op1(ast0::Op1::Not, ..)));
+    let m = parse_prog("!(f x)").unwrap();
+    assert!(matches!(*m, ast0::Term::Op1(ast0::Op1::Deref, ..)));
+}
+
+#[test]
+fn literals() {
+    parses_as("2147483647", "2147483647");
+    parses_as("1e999", "1e999");
+    parses_as("-1e999", "-1e999");
+    parses_as("1.5e-7", "1.5e-7");
+    let e = parse_prog("1 + 2147483648").unwrap_err();
+    assert_eq!(
+        e.line_col,
+        pest::error::LineColLocation::Span((1, 5), (1, 15))
+    );
+    assert!(
+        e.to_string()
+            .contains("integer literal 2147483648 is out of range")
+    );
+}
+
+#[test]
+fn examples_round_trip() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let src = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        round_trip(&src).unwrap();
+    }
+}
+
+#[test]
+fn negative_literals_print() {
+    let ctx = Ctx::default();
+    for m in [
+        ast0::int(-5),
+        ast0::int(i32::MIN),
+        ast0::float(-0.0),
+        ast0::float(f64::NAN),
+        ast0::float(f64::NEG_INFINITY),
+    ] {
+        let n = parse_prog(&trans(&m, &ctx).to_string()).unwrap();
+        assert!(alpha_eq(&trans(&syntactic(&m), &ctx), &trans(&n, &ctx)));
+    }
+}
+
+#[test]
+fn keywords() {
+    assert!(parse_prog("!r perform").is_err());
+    assert!(parse_prog("(perform) x").is_err());
+    parses_as("let x := 1 in(x)", "let x := 1 in x");
+    parses_as("inx + fun_ + return'", "inx + fun_ + return'");
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use rust_test::{eval::Value, profile::Profiler};
+
+fn rows(profiler: &Profiler) -> Vec<(String, usize, usize, usize)> {
+    profiler
+        .table()
+        .into_iter()
+        .map(|row| (row.name, row.calls, row.self_steps, row.total_steps))
+        .collect()
+}
+
+#[test]
+fn self_and_total_steps() {
+    let src = "let rec fact n := if n == 0 then 1 else n * fact (n - 1) in
+let sq x := x * x in
+sq (fact 3) + sq 2";
+    let tm = common::source(src);
+    let mut profiler = Profiler::new(&tm);
+    assert!(matches!(profiler.run(1000), Some(Ok(Value::Int(40)))));
+    assert_eq!(
+        rows(&profiler),
+        vec![
+            (String::from("main"), 0, 23, 101),
+            (String::from("fact"), 4, 68, 68),
+            (String::from("sq"), 2, 10, 10),
+        ]
+    );
+    assert_eq!(profiler.folded(), "main 23\nmain;fact 68\nmain;sq 10\n");
+}
+
+#[test]
+fn nested_calls() {
+    let src = "let g x := x + 1 in
+let f x := g x * g x in
+f (f 1)";
+    let tm = common::source(src);
+    let mut profiler = Profiler::new(&tm);
+    assert!(matches!(profiler.run(1000), Some(Ok(Value::Int(25)))));
+    let rows = rows(&profiler);
+    assert_eq!(rows[1].0, "f");
+    assert_eq!(rows[1].1, 2);
+    assert_eq!(rows[2].0, "g");
+    assert_eq!(rows[2].1, 4);
+    // g only runs inside f
+    assert_eq!(rows[1].3, rows[1].2 + rows[2].3);
+    assert!(profiler.folded().contains("main;f;g "));
+}
+
+#[test]
+fn recursion_is_counted_once() {
+    let tm = common::example("test2");
+    let mut profiler = Profiler::new(&tm);
+    assert!(matches!(profiler.run(100_000), Some(Ok(Value::Int(91)))));
+    let rows = rows(&profiler);
+    assert_eq!(rows[1].0, "mccarthy");
+    assert_eq!(rows[1].2, rows[1].3);
+    let folded: usize = profiler
+        .folded()
+        .lines()
+        .map(|l| l.rsplit_once(' ').unwrap().1.parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(rows[0].3, folded);
+}
+
+#[test]
+fn step_limit() {
+    let tm = common::source("let rec f x := f x in f 0");
+    let mut profiler = Profiler::new(&tm);
+    assert!(profiler.run(100).is_none());
+    assert_eq!(profiler.folded().lines().count(), 2);
+}
+
+#[test]
+fn long_tail_recursive_loop() {
+    let src = "let rec loop i := if i == 0 then 0 else loop (i - 1) in loop 200000";
+    let tm = common::source(src);
+    let mut profiler = Profiler::new(&tm);
+    assert!(matches!(profiler.run(10_000_000), Some(Ok(Value::Int(0)))));
+    assert_eq!(
+        rows(&profiler),
+        vec![
+            (String::from("main"), 0, 8, 3_200_016),
+            (String::from("loop"), 200_001, 3_200_008, 3_200_008),
+        ]
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use ahash::HashMap;
+use rust_test::{
+    error::{Error, Pos},
+    eval::eval,
+    smallstep::{reduce, step},
+};
+use std::{cell::RefCell, fs};
+
+fn run(src: &str) -> Result<String, Error> {
+    reduce(&common::source(src))
+        .map(|m| m.to_string())
+        .map_err(Error::from)
+}
+
+fn trace(src: &str) -> Vec<String> {
+    let mut m = common::source(src);
+    let mut terms = vec![m.to_string()];
+    while let Some(m1) = step(&m).unwrap() {
+        m = m1;
+        terms.push(m.to_string());
+    }
+    terms
+}
+
+#[test]
+fn pretty_printer() {
+    let cases = [
+        "(1 + 2) * 3 - 4 / 2",
+        "1 - (2 - 3)",
+        "f (g x) y",
+        "(-(f x)) + 1",
+        "(fun x => x + 1) 1",
+        "f (fun x => x) (let y := 1 in y)",
+        "if a && (b || c) then ref 1 else !r",
+        "r := (x := 1) + 1",
+        "let rec f x := f x in f 0",
+        "try raise (x + 1) with e => e",
+    ];
+    for src in cases {
+        let src = format!(
+            "let a := true in let b := a in let c := a in let r := ref 0 in let x := r in let f := fun x => x in let g := f in let y := 2 in {}",
+            src
+        );
+        let m = common::source(&src);
+        assert_eq!(m.to_string(), src);
+    }
+}
+
+#[test]
+fn steps() {
+    assert_eq!(
+        trace("let f x := x * 2 in f (1 + 2)"),
+        [
+            "let f := fun x => x * 2 in f (1 + 2)",
+            "(fun x => x * 2) (1 + 2)",
+            "(fun x => x * 2) 3",
+            "3 * 2",
+            "6"
+        ]
+    );
+    assert_eq!(
+        trace("try 1 + raise 2 with x => x * 10"),
+        [
+            "try 1 + raise 2 with x => x * 10",
+            "try raise 2 with x => x * 10",
+            "2 * 10",
+            "20"
+        ]
+    );
+}
+
+#[test]
+fn errors() {
+    let pos = Pos { line: 1, col: 8 };
+    assert_eq!(run("1 + (1 / 0)"), Err(Error::DivByZero(pos)));
+    assert_eq!(run("try 1 / 0 with x => x"), Ok(String::from("1")));
+    assert_eq!(run("false && 1 / 0 == 1"), Ok(String::from("false")));
+    assert_eq!(
+        run("1 + raise 2"),
+        Err(Error::Uncaught(
+            String::from("Int(2)"),
+            Pos { line: 1, col: 5 }
+        ))
+    );
+}
+
+#[test]
+fn unsupported() {
+    let unsupported = |what: &str| {
+        Err(Error::Unsupported(format!(
+            "{} in the small step semantics",
+            what
+        )))
+    };
+    for (f, what) in [("test7", "references"), ("test8", "effect handlers")] {
+        let src = fs::read_to_string(format!("examples/{}.txt", f)).unwrap();
+        assert_eq!(run(&src), unsupported(what));
+    }
+    assert_eq!(run("1 + callcc (fun k => k 1)"), unsupported("callcc"));
+    assert_eq!(run("try ref 1 with e => e"), unsupported("references"));
+}
+
+#[test]
+fn examples_agree_with_eval() {
+    for f in ["test0", "test2", "test3", "test5", "test6"] {
+        let tm = common::example(f);
+        let expected = eval(&RefCell::new(HashMap::default()), &tm).unwrap();
+        assert_eq!(
+            format!("{:?}", expected),
+            format!("{:?}", reduce(&tm).unwrap())
+        );
+    }
+}